@@ -0,0 +1,256 @@
+use std::path::{Component, PathBuf};
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::resources::config::{ConfigError, ConfigOverrides, SimConfig};
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "automata-rps",
+    about = "A battle simulation of Rock, Paper & Scissors"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Run without a window and print the winner when the match ends
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Give up on a headless match after this many ticks
+    #[arg(long, global = true, default_value_t = 60 * 60 * 10)]
+    pub max_ticks: u32,
+
+    /// Seed for every random draw; the same seed replays the same match
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Factions and who beats whom, as a RON file
+    #[arg(long, global = true, default_value = "assets/factions/classic.ron")]
+    pub factions: PathBuf,
+
+    /// Write the population of every faction over time to this CSV file
+    /// when the match ends
+    #[arg(long)]
+    pub csv: Option<PathBuf>,
+
+    /// Simulation settings, as a RON file
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Reload the config file whenever it changes; it must live in `assets/`
+    #[arg(long, requires = "config")]
+    pub watch: bool,
+
+    /// Start from the entities of this scenario file instead of a random
+    /// layout
+    #[arg(long, value_name = "PATH")]
+    pub scenario: Option<PathBuf>,
+
+    /// Save the world as a scenario file as soon as the match starts
+    #[arg(long, value_name = "PATH")]
+    pub save_scenario: Option<PathBuf>,
+
+    /// Record the match into this replay file; the settings are saved once,
+    /// so it cannot be combined with `--watch`
+    #[arg(long, value_name = "PATH", conflicts_with = "watch")]
+    pub record: Option<PathBuf>,
+
+    /// Play a recorded match again; it brings its own seed, settings and
+    /// factions
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["seed", "config", "factions", "overrides", "record", "scenario"])]
+    pub replay: Option<PathBuf>,
+
+    /// Override a single setting, e.g. `--set max_speed=80`; repeatable
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE", value_parser = parse_override)]
+    pub overrides: Vec<(String, String)>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Play many headless matches and report how often each faction wins
+    Tournament(TournamentArgs),
+    /// Play a tournament for every combination of settings and report how
+    /// fair and how long matches are
+    Sweep(SweepArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct SweepArgs {
+    /// Setting to vary and its values, e.g. `vision_max=100,125,150` or
+    /// `max_speed=40..80:10`; repeat to build a grid
+    #[arg(long, value_name = "KEY=VALUES", value_parser = parse_sweep, required = true)]
+    pub vary: Vec<(String, Vec<String>)>,
+
+    /// Matches played for every combination
+    #[arg(long, default_value_t = 20)]
+    pub seeds: u64,
+
+    /// Seed of the first match of every combination
+    #[arg(long, default_value_t = 0)]
+    pub first_seed: u64,
+
+    /// Matches played at the same time (defaults to the number of CPUs)
+    #[arg(long)]
+    pub jobs: Option<usize>,
+
+    /// Write one line per combination to this CSV file
+    #[arg(long, value_name = "PATH")]
+    pub csv: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct TournamentArgs {
+    /// Number of matches to play
+    #[arg(long, default_value_t = 100)]
+    pub matches: u64,
+
+    /// Seed of the first match, the next ones use the following seeds
+    #[arg(long, default_value_t = 0)]
+    pub first_seed: u64,
+
+    /// Matches played at the same time (defaults to the number of CPUs)
+    #[arg(long)]
+    pub jobs: Option<usize>,
+
+    /// Write every match and the win rates to this JSON file
+    #[arg(long, value_name = "PATH")]
+    pub json: Option<PathBuf>,
+
+    /// Write one line per match to this CSV file
+    #[arg(long, value_name = "PATH")]
+    pub matches_csv: Option<PathBuf>,
+
+    /// Write the win rate of every faction to this CSV file
+    #[arg(long, value_name = "PATH")]
+    pub summary_csv: Option<PathBuf>,
+}
+
+impl Cli {
+    /// The config file (or the defaults) with every `--set` applied, checked
+    /// against the number of `factions`.
+    pub fn sim_config(&self, factions: usize) -> Result<SimConfig, ConfigError> {
+        let mut config = match &self.config {
+            Some(path) => SimConfig::load(path)?,
+            None => SimConfig::default(),
+        };
+        ConfigOverrides(self.overrides.clone()).apply(&mut config)?;
+        config.validate(factions)?;
+        Ok(config)
+    }
+
+    /// Asset path of the config file to hot reload, if `--watch` was given.
+    pub fn watched_config(&self) -> Result<Option<String>, String> {
+        let (true, Some(path)) = (self.watch, &self.config) else {
+            return Ok(None);
+        };
+        // `./assets/config.ron` is the same file as `assets/config.ron`
+        let path: PathBuf = path
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .collect();
+        match path.strip_prefix("assets") {
+            Ok(relative) => Ok(Some(relative.to_string_lossy().into_owned())),
+            Err(_) => Err(format!(
+                "{} is not inside assets/, it cannot be watched",
+                path.display()
+            )),
+        }
+    }
+}
+
+// `key=a,b,c` or `key=start..end:step`, both ends included
+fn parse_sweep(arg: &str) -> Result<(String, Vec<String>), String> {
+    let (key, values) = parse_override(arg)?;
+
+    if let Some((range, step)) = values.split_once(':') {
+        let bad = || format!("expected START..END:STEP, got `{values}`");
+        let (start, end) = range.split_once("..").ok_or_else(bad)?;
+        let start: f64 = start.trim().parse().map_err(|_| bad())?;
+        let end: f64 = end.trim().parse().map_err(|_| bad())?;
+        let step: f64 = step.trim().parse().map_err(|_| bad())?;
+        if step <= 0. || end < start {
+            return Err(bad());
+        }
+
+        let count = ((end - start) / step + 1e-9).floor() as usize + 1;
+        let values = (0..count)
+            .map(|i| (start + step * i as f64).to_string())
+            .collect();
+        return Ok((key, values));
+    }
+
+    let values: Vec<String> = values.split(',').map(|v| v.trim().to_owned()).collect();
+    if values.iter().any(String::is_empty) {
+        return Err(format!("empty value in `{arg}`"));
+    }
+    Ok((key, values))
+}
+
+fn parse_override(arg: &str) -> Result<(String, String), String> {
+    arg.split_once('=')
+        .map(|(key, value)| (key.trim().to_owned(), value.to_owned()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got `{arg}`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sweep(arg: &str) -> Vec<String> {
+        parse_sweep(arg).unwrap().1
+    }
+
+    fn watched(args: &[&str]) -> Result<Option<String>, String> {
+        let args = ["automata-rps"].iter().chain(args);
+        Cli::try_parse_from(args).unwrap().watched_config()
+    }
+
+    #[test]
+    fn watched_configs_live_in_assets() {
+        let relative = Ok(Some("config.ron".to_owned()));
+        assert_eq!(
+            watched(&["--config", "assets/config.ron", "--watch"]),
+            relative
+        );
+        assert_eq!(
+            watched(&["--config", "./assets/config.ron", "--watch"]),
+            relative
+        );
+        assert_eq!(
+            watched(&["--config", "assets/./config.ron", "--watch"]),
+            relative
+        );
+        assert!(watched(&["--config", "config.ron", "--watch"]).is_err());
+        assert_eq!(watched(&["--config", "config.ron"]), Ok(None));
+    }
+
+    #[test]
+    fn sweep_lists_and_ranges() {
+        assert_eq!(
+            parse_sweep("fov= 90, 180 ,360").unwrap(),
+            (
+                "fov".to_owned(),
+                vec!["90".into(), "180".into(), "360".into()]
+            )
+        );
+        assert_eq!(sweep("max_speed=40..80:20"), ["40", "60", "80"]);
+        assert_eq!(sweep("max_speed=40..90:20"), ["40", "60", "80"]);
+        assert_eq!(sweep("fov=0.1..0.3:0.1").len(), 3);
+        assert_eq!(sweep("max_speed=5..5:1"), ["5"]);
+    }
+
+    #[test]
+    fn sweep_rejects_malformed_values() {
+        for arg in [
+            "max_speed",
+            "max_speed=40,,80",
+            "max_speed=40..80:0",
+            "max_speed=80..40:10",
+            "max_speed=40..x:10",
+            "max_speed=40-80:10",
+        ] {
+            assert!(parse_sweep(arg).is_err(), "{arg} should be rejected");
+        }
+    }
+}
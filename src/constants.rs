@@ -1,2 +1,32 @@
-pub const SPEED_FACTOR: f32 = 0.5;
+// Defaults of `SimConfig`, each of them can be overridden from a config file.
+pub const TICK_RATE: f32 = 60.;
+pub const MAX_SPEED: f32 = 60.;
+pub const MAX_ACCEL: f32 = 240.;
 pub const SPRITE_SIZE: f32 = 20.;
+pub const REGIONS: usize = 48;
+pub const ENTITIES_PER_REGION: usize = 2;
+pub const REGION_RADIUS: f32 = 60.;
+pub const VISION_MIN: f32 = 75.;
+pub const VISION_MAX: f32 = 125.;
+pub const FOV: f32 = 270.;
+pub const OCCLUSION: bool = false;
+pub const MEMORY_SECS: f32 = 3.;
+pub const SEEK_WEIGHT: f32 = 0.5;
+pub const FLEE_WEIGHT: f32 = 1.;
+pub const SEPARATION_WEIGHT: f32 = 0.5;
+pub const WANDER_WEIGHT: f32 = 0.2;
+pub const EXPLORE_WEIGHT: f32 = 0.6;
+pub const WANDER_TURN: f32 = 120.;
+pub const REST_SECS: f32 = 0.5;
+pub const WANDER_SPEED: f32 = 0.6;
+pub const CHASE_SPEED: f32 = 1.;
+pub const FLEE_SPEED: f32 = 1.;
+pub const REST_SPEED: f32 = 0.2;
+pub const ENERGY_DRAIN: f32 = 0.1;
+pub const ENERGY_REGAIN: f32 = 0.2;
+pub const EXHAUSTED_SPEED: f32 = 0.4;
+pub const MAX_HEALTH: f32 = 100.;
+pub const CONTACT_DAMAGE: f32 = 200.;
+pub const INVULNERABLE_SECS: f32 = 1.;
+pub const ARENA_WIDTH: f32 = 1080. / 2.;
+pub const ARENA_HEIGHT: f32 = 1920. / 2.;
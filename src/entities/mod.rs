@@ -1,79 +1,103 @@
-use std::fmt::Debug;
-
 use bevy::{math::Vec2, prelude::Component};
 
-use crate::add_components;
-
-#[derive(Debug)]
-pub enum Mock {
-    Rock,
-    Paper,
-    Scissors,
-}
-
-#[derive(Component, Clone, Debug, PartialEq, Eq, Copy)]
-pub struct Rock;
-
-#[derive(Component, Clone, Debug, PartialEq, Eq, Copy)]
-pub struct Paper;
+/// Index of the faction an entity fights for, as declared in
+/// [`crate::resources::factions::Factions`].
+#[derive(Component, Clone, Debug, Default, PartialEq, Eq, Hash, Copy)]
+pub struct Faction(pub usize);
 
-#[derive(Component, Clone, Debug, PartialEq, Eq, Copy)]
-pub struct Scissors;
-
-#[derive(Component)]
-pub struct Enemy<T: Component>(pub T);
-
-#[derive(Component)]
-pub struct Target<T: Component>(pub T);
+/// Spawn order of an entity within its match. Systems whose outcome depends
+/// on iteration order walk entities by `Id`, so a match plays out the same
+/// whatever else (sprites, interpolation) is attached to them.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Id(pub u32);
 
 #[derive(Component, Clone)]
 pub struct Vision(pub f32);
 
+/// Marks a [`Vision`] that was given (by a scenario) rather than drawn from
+/// the configured range, so config reloads leave it as it is.
+#[derive(Component)]
+pub struct FixedVision;
+
 #[derive(Component)]
 pub struct Velocity(pub Vec2);
 
-pub trait HasEnemy {
-    type Enemy: Component + Debug;
-}
+/// Stamina from 0 (exhausted) to 1 (rested). Chasing and fleeing drain it,
+/// anything else brings it back, and the top speed shrinks with it.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct Energy(pub f32);
 
-pub trait HasTarget {
-    type Target: Component + Debug;
+impl Default for Energy {
+    fn default() -> Self {
+        Self(1.)
+    }
 }
 
-pub trait HasSprite {
-    const PREFIX: &str = "sprites/";
-    fn img(&self) -> String;
-    fn sound(&self) -> String;
+/// Hit points, only used with `ConversionMode::Health`: touching enemies
+/// wear them down and the entity changes sides at 0.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct Health {
+    pub points: f32,
+    /// Seconds left during which it takes no damage.
+    pub invulnerable: f32,
+    /// Seconds left of the tint showing it was just hit.
+    pub flash: f32,
 }
 
-add_components!(Rock, Paper, Scissors);
-add_components!(Paper, Scissors, Rock);
-add_components!(Scissors, Rock, Paper);
-
-impl HasSprite for Rock {
-    fn img(&self) -> String {
-        format!("{}/rock.png", Self::PREFIX)
+impl Health {
+    pub fn new(points: f32) -> Self {
+        Self {
+            points,
+            invulnerable: 0.,
+            flash: 0.,
+        }
     }
+}
 
-    fn sound(&self) -> String {
-        "sounds/rock.ogg".to_owned()
-    }
+/// What an entity noticed lately, see `perceive`. Sightings are kept for
+/// `SimConfig::memory_secs` once the entity is out of sight.
+#[derive(Component, Clone, Debug, Default)]
+pub struct Perception {
+    /// Closest entity it chases.
+    pub target: Option<Sighting>,
+    /// Closest entity it runs from.
+    pub enemy: Option<Sighting>,
 }
 
-impl HasSprite for Paper {
-    fn img(&self) -> String {
-        format!("{}/paper.png", Self::PREFIX)
-    }
-    fn sound(&self) -> String {
-        "sounds/paper.ogg".to_owned()
-    }
+/// What an entity is busy with, chosen each tick from its [`Perception`].
+/// Only the matching steering behaviour applies, at
+/// `SimConfig::max_speed` scaled by the state's speed.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Behavior {
+    /// Nobody in sight, roaming around.
+    #[default]
+    Wander,
+    Chase,
+    /// An enemy is closer than any target.
+    Flee,
+    /// Catching its breath after a conversion, unless an enemy shows up.
+    Rest {
+        left: f32,
+    },
 }
 
-impl HasSprite for Scissors {
-    fn img(&self) -> String {
-        format!("{}/scissors.png", Self::PREFIX)
-    }
-    fn sound(&self) -> String {
-        "sounds/scissors.ogg".to_owned()
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sighting {
+    pub position: Vec2,
+    /// Seconds since it was last seen there.
+    pub age: f32,
+}
+
+impl Sighting {
+    /// 1 while in sight, fading to 0 as the memory of it goes (at once
+    /// without any memory).
+    pub fn certainty(&self, memory_secs: f32) -> f32 {
+        if memory_secs > 0. {
+            (1. - self.age / memory_secs).max(0.)
+        } else if self.age > 0. {
+            0.
+        } else {
+            1.
+        }
     }
 }
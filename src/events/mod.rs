@@ -1,7 +1,29 @@
+use std::time::Duration;
+
 use bevy::prelude::{Entity, Event};
 
+use crate::entities::Faction;
+
 #[derive(Event)]
 pub struct DangerEvent {
     pub actor: Entity,
     pub target: Entity,
 }
+
+#[derive(Event)]
+pub struct ConversionEvent {
+    pub actor: Entity,
+    pub target: Entity,
+    pub faction: Faction,
+}
+
+/// Short-lived message shown on screen.
+#[derive(Event)]
+pub struct ToastEvent(pub String);
+
+#[derive(Event)]
+pub struct GameOverEvent {
+    pub winner: Faction,
+    pub elapsed: Duration,
+    pub conversions: u32,
+}
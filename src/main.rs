@@ -1,23 +1,148 @@
+pub mod cli;
 pub mod constants;
 pub mod entities;
 pub mod events;
 pub mod plugins;
 pub mod resources;
+pub mod sweep;
+pub mod tournament;
 pub mod utils;
 
 use bevy::prelude::*;
 use bevy_rand::{plugin::EntropyPlugin, prelude::WyRand};
-use plugins::game::GameplayPlugin;
+use clap::Parser;
+use cli::{Cli, Command};
+use plugins::{
+    game::GameplayPlugin,
+    graphics::GraphicsPlugin,
+    headless::HeadlessPlugin,
+    replay::{RecordPlugin, ReplayPlugin},
+};
+use resources::{
+    config::ConfigOverrides, factions::Factions, replay::Replay, scenario::Scenario, Arena,
+    CsvExport, ScenarioExport, Seed,
+};
 
-fn setup(mut commands: Commands) {
-    commands.spawn(Camera2d);
+fn main() {
+    let cli = Cli::parse();
+    let watch = or_exit(cli.watched_config());
+
+    // a replay brings its own seed, settings and factions, checked like the
+    // command line ones in case the file was edited by hand
+    let replay = cli.replay.as_ref().map(|path| {
+        let checked = Replay::load(path)
+            .map_err(|e| e.to_string())
+            .and_then(|replay| {
+                let factions = Factions::new(replay.factions.clone()).map_err(|e| e.to_string())?;
+                replay
+                    .config
+                    .validate(factions.len())
+                    .map_err(|e| e.to_string())?;
+                if let Some(scenario) = &replay.scenario {
+                    scenario
+                        .check(&factions, &Arena::from(&replay.config))
+                        .map_err(|e| e.to_string())?;
+                }
+                Ok(replay)
+            });
+        or_exit(checked.map_err(|e| format!("{}: {e}", path.display())))
+    });
+    let (factions, config, seed) = match &replay {
+        Some(replay) => (
+            or_exit(Factions::new(replay.factions.clone())),
+            replay.config.clone(),
+            replay.seed,
+        ),
+        None => {
+            let factions = or_exit(
+                Factions::load(&cli.factions)
+                    .map_err(|e| format!("{}: {e}", cli.factions.display())),
+            );
+            let config = or_exit(cli.sim_config(factions.len()));
+            (factions, config, cli.seed.unwrap_or_else(rand::random))
+        }
+    };
+
+    let scenario = match &replay {
+        Some(replay) => replay.scenario.clone(),
+        None => cli.scenario.as_ref().map(|path| {
+            or_exit(
+                Scenario::load(path)
+                    .and_then(|scenario| {
+                        scenario
+                            .check(&factions, &Arena::from(&config))
+                            .map(|()| scenario)
+                    })
+                    .map_err(|e| format!("{}: {e}", path.display())),
+            )
+        }),
+    };
+
+    match &cli.command {
+        Some(Command::Tournament(args)) => {
+            or_exit(tournament::check_seeds(args.first_seed, args.matches));
+            tournament::run(args, &factions, &config, cli.max_ticks);
+            return;
+        }
+        Some(Command::Sweep(args)) => {
+            or_exit(tournament::check_seeds(args.first_seed, args.seeds));
+            sweep::run(args, &factions, &config, cli.max_ticks);
+            return;
+        }
+        None => {}
+    }
+
+    let arena = Arena::from(&config);
+    let mut app = App::new();
+    app.insert_resource(Seed(seed))
+        .insert_resource(factions)
+        .insert_resource(config)
+        .insert_resource(ConfigOverrides(cli.overrides.clone()))
+        .insert_resource(arena);
+    if let Some(scenario) = scenario {
+        app.insert_resource(scenario);
+    }
+    if let Some(path) = cli.save_scenario {
+        app.insert_resource(ScenarioExport {
+            path,
+            on_start: true,
+        });
+    }
+    if let Some(path) = cli.csv {
+        app.insert_resource(CsvExport {
+            path,
+            on_game_over: true,
+        });
+    }
+
+    if cli.headless {
+        app.add_plugins(HeadlessPlugin {
+            max_ticks: cli.max_ticks,
+            report: true,
+        });
+    } else {
+        app.add_plugins(GraphicsPlugin { watch });
+    }
+
+    app.add_plugins(GameplayPlugin)
+        .add_plugins(EntropyPlugin::<WyRand>::with_seed(seed.to_le_bytes()));
+
+    if let Some(path) = cli.record {
+        app.add_plugins(RecordPlugin { path });
+    }
+    if let Some(replay) = replay {
+        app.add_plugins(ReplayPlugin {
+            replay,
+            interactive: !cli.headless,
+        });
+    }
+
+    app.run();
 }
 
-fn main() {
-    App::new()
-        .add_plugins(GameplayPlugin)
-        .add_plugins(EntropyPlugin::<WyRand>::default())
-        .insert_resource(ClearColor(Color::Srgba(Srgba::rgb(240.0, 240.0, 240.0)))) // background
-        .add_systems(Startup, setup)
-        .run();
+fn or_exit<T>(result: Result<T, impl std::fmt::Display>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    })
 }
@@ -0,0 +1,75 @@
+use bevy::{math::vec2, prelude::*};
+
+use crate::resources::{factions::Factions, Arena, DebugState, PopulationHistory};
+
+/// Population of every faction over time, drawn in the bottom right corner
+/// together with the HUD.
+pub struct ChartPlugin;
+
+impl Plugin for ChartPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, draw_chart);
+    }
+}
+
+const SIZE: Vec2 = vec2(200., 100.);
+const MARGIN: f32 = 10.;
+
+// used for factions without a tint
+const PALETTE: [Color; 6] = [
+    Color::srgb(0.45, 0.45, 0.45),
+    Color::srgb(0.2, 0.5, 0.9),
+    Color::srgb(0.9, 0.25, 0.25),
+    Color::srgb(0.3, 0.75, 0.3),
+    Color::srgb(0.85, 0.6, 0.1),
+    Color::srgb(0.6, 0.3, 0.8),
+];
+
+fn draw_chart(
+    mut gizmos: Gizmos,
+    arena: Res<Arena>,
+    debug: Res<DebugState>,
+    factions: Res<Factions>,
+    history: Res<PopulationHistory>,
+) {
+    if !debug.hud || history.samples.len() < 2 {
+        return;
+    }
+
+    let origin = vec2(
+        arena.width / 2. - SIZE.x - MARGIN,
+        -arena.height / 2. + MARGIN,
+    );
+    gizmos.rect_2d(
+        Isometry2d::from_translation(origin + SIZE / 2.),
+        SIZE,
+        Color::BLACK,
+    );
+
+    let population = history
+        .samples
+        .iter()
+        .map(|(_, counts)| counts.iter().sum::<usize>())
+        .max()
+        .unwrap_or(1)
+        .max(1) as f32;
+    let last = (history.samples.len() - 1) as f32;
+    // no point in drawing more vertices than the panel has pixels
+    let stride = history.samples.len().div_ceil(SIZE.x as usize);
+
+    for faction in factions.iter() {
+        let color = match factions.def(faction).tint {
+            Some((r, g, b)) => Color::srgb(r, g, b),
+            None => PALETTE[faction.0 % PALETTE.len()],
+        };
+        let points = history
+            .samples
+            .iter()
+            .enumerate()
+            .step_by(stride)
+            .map(|(i, (_, counts))| {
+                origin + vec2(i as f32 / last, counts[faction.0] as f32 / population) * SIZE
+            });
+        gizmos.linestrip_2d(points, color);
+    }
+}
@@ -1,31 +1,59 @@
-use std::ops::Deref;
+use std::{
+    collections::HashSet,
+    ops::Deref,
+    time::{Duration, Instant},
+};
 
-use bevy::prelude::*;
+use bevy::{input::common_conditions::input_just_pressed, prelude::*};
 
 use crate::{
-    entities::{Paper, Rock, Scissors},
-    resources::{DebugState, GameControl, GameState, GenerableRegions},
+    entities::{Behavior, Energy, Faction, Vision},
+    resources::{
+        config::SimConfig, factions::Factions, replay::Replay, DebugState, GameControl, GameState,
+        GenerableRegions,
+    },
 };
 
+use super::game::{export_population, export_scenario, game_over_pending, run_tick};
+
 pub struct DebugPlugin;
 
 impl Plugin for DebugPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.insert_resource(DebugState::default())
             .insert_resource(GameControl::default())
-            .add_systems(Startup, debug_regions)
+            .add_systems(
+                OnTransition {
+                    exited: GameState::LoadingRes,
+                    entered: GameState::InGame,
+                },
+                debug_regions,
+            )
             .add_systems(
                 Update,
                 (
+                    debug_radius,
                     toggle_view_regions,
-                    toggle_rock,
-                    toggle_rock_radius,
-                    toggle_paper,
-                    toggle_paper_radius,
-                    toggle_scissors,
-                    toggle_scissors_radius,
+                    toggle_hud,
+                    toggle_behavior,
+                    draw_behavior,
+                    toggle_energy,
+                    draw_energy,
+                    toggle_factions,
+                    toggle_factions_radius,
                     control_time,
+                    control_speed,
+                    control_step,
+                    control_skip,
+                    // or `control_time` would undo the end of the match
+                    skip_to_end.after(control_time),
                     control_sound,
+                    control_volume,
+                    // a replay goes back to its start with `Home` instead, so
+                    // it plays the recorded seed again
+                    control_restart.run_if(not(resource_exists::<Replay>)),
+                    export_population.run_if(input_just_pressed(KeyCode::KeyE)),
+                    export_scenario.run_if(input_just_pressed(KeyCode::F5)),
                 ),
             );
     }
@@ -34,30 +62,40 @@ impl Plugin for DebugPlugin {
 #[derive(Component)]
 pub struct DebugPoint;
 
+#[derive(Component, Default)]
+pub struct VisionRing;
+
 #[derive(Bundle)]
 pub struct DebugRadius {
+    pub ring: VisionRing,
     pub mesh: Mesh2d,
     pub material: MeshMaterial2d<ColorMaterial>,
     pub visible: Visibility,
 }
 
-// just initialize the debug points
+// just initialize the debug points, dropping the ones of a previous match
 fn debug_regions(
     regions: Res<GenerableRegions>,
+    previous: Query<Entity, With<DebugPoint>>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
+    for entity in previous.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
     let dot = meshes.add(Circle::new(2.));
     let color = materials.add(Color::linear_rgb(255., 0., 0.));
-    for &(x, y, r) in regions.0.deref() {
+    for region in regions.0.deref() {
+        let (center, r) = (region.center, region.radius);
         let border = meshes.add(Annulus::new(r - 1., r + 1.));
         commands
             .spawn((
                 DebugPoint,
                 Mesh2d(dot.clone()),
                 MeshMaterial2d(color.clone()),
-                Transform::from_xyz(x, y, 100.),
+                Transform::from_xyz(center.x, center.y, 100.),
                 Visibility::Hidden,
             ))
             .with_children(|c| {
@@ -70,44 +108,54 @@ fn debug_regions(
     }
 }
 
-fn toggle_view_regions(
-    mut res: ResMut<DebugState>,
-    mut query: Query<&mut Visibility, With<DebugPoint>>,
-    keys: Res<ButtonInput<KeyCode>>,
+// every entity carries its (hidden) vision radius as a child, redrawn
+// whenever the radius changes
+fn debug_radius(
+    query: Query<(Entity, &Vision, Option<&Children>), Changed<Vision>>,
+    rings: Query<(), With<VisionRing>>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
     if query.is_empty() {
         return;
     }
 
-    if keys.just_pressed(KeyCode::KeyD) {
-        res.points = !res.points;
-    }
-
-    let new_vis = res.points;
-
-    for mut vis in query.iter_mut() {
-        *vis = if new_vis {
-            Visibility::Visible
-        } else {
-            Visibility::Hidden
-        };
+    let material = MeshMaterial2d(materials.add(Color::linear_rgb(255., 0., 0.)));
+    for (entity, vision, children) in query.iter() {
+        for &child in children.into_iter().flatten() {
+            if rings.contains(child) {
+                commands.entity(child).despawn_recursive();
+            }
+        }
+
+        let r = vision.0;
+        let mesh = meshes.add(Annulus::new(r - 1., r + 1.));
+        commands.entity(entity).with_children(|c| {
+            c.spawn(DebugRadius {
+                ring: VisionRing,
+                mesh: Mesh2d(mesh),
+                material: material.clone(),
+                visible: Visibility::Hidden,
+            });
+        });
     }
 }
 
-fn toggle_rock(
+fn toggle_view_regions(
     mut res: ResMut<DebugState>,
-    mut query: Query<&mut Visibility, With<Rock>>,
+    mut query: Query<&mut Visibility, With<DebugPoint>>,
     keys: Res<ButtonInput<KeyCode>>,
 ) {
     if query.is_empty() {
         return;
     }
 
-    if !keys.pressed(KeyCode::ShiftLeft) && keys.just_pressed(KeyCode::Digit1) {
-        res.rocks = !res.rocks;
+    if keys.just_pressed(KeyCode::KeyD) {
+        res.points = !res.points;
     }
 
-    let new_vis = res.rocks;
+    let new_vis = res.points;
 
     for mut vis in query.iter_mut() {
         *vis = if new_vis {
@@ -118,129 +166,155 @@ fn toggle_rock(
     }
 }
 
-fn toggle_rock_radius(
-    mut res: ResMut<DebugState>,
-    mut query: Query<&mut Visibility>,
-    rock_query: Query<&Children, With<Rock>>,
-    keys: Res<ButtonInput<KeyCode>>,
-) {
-    if query.is_empty() {
-        return;
+fn toggle_hud(mut res: ResMut<DebugState>, keys: Res<ButtonInput<KeyCode>>) {
+    if keys.just_pressed(KeyCode::KeyH) {
+        res.hud = !res.hud;
     }
+}
 
-    if keys.pressed(KeyCode::ShiftLeft) && keys.just_pressed(KeyCode::Digit1) {
-        res.radius_rocks = !res.radius_rocks;
+fn toggle_behavior(mut res: ResMut<DebugState>, keys: Res<ButtonInput<KeyCode>>) {
+    if keys.just_pressed(KeyCode::KeyO) {
+        res.behavior = !res.behavior;
     }
+}
 
-    let new_vis = res.radius_rocks;
-    let children = rock_query.iter().flatten();
-    let mut iter = query.iter_many_mut(children);
-    while let Some(mut vis) = iter.fetch_next() {
-        *vis = if new_vis {
-            Visibility::Visible
-        } else {
-            Visibility::Hidden
-        };
+fn behavior_color(behavior: Behavior) -> Color {
+    match behavior {
+        Behavior::Wander => Color::srgb(0.7, 0.7, 0.7),
+        Behavior::Chase => Color::srgb(0.2, 0.9, 0.2),
+        Behavior::Flee => Color::srgb(1., 0.2, 0.2),
+        Behavior::Rest { .. } => Color::srgb(0.2, 0.6, 1.),
     }
 }
 
-fn toggle_paper_radius(
-    mut res: ResMut<DebugState>,
-    mut query: Query<&mut Visibility>,
-    rock_query: Query<&Children, With<Paper>>,
-    keys: Res<ButtonInput<KeyCode>>,
+fn draw_behavior(
+    res: Res<DebugState>,
+    config: Res<SimConfig>,
+    query: Query<(&Transform, &Behavior, &Visibility)>,
+    mut gizmos: Gizmos,
 ) {
-    if query.is_empty() {
+    if !res.behavior {
         return;
     }
 
-    if keys.pressed(KeyCode::ShiftLeft) && keys.just_pressed(KeyCode::Digit2) {
-        res.radius_papers = !res.radius_papers;
+    for (transform, &behavior, vis) in query.iter() {
+        if *vis != Visibility::Hidden {
+            gizmos.circle_2d(
+                transform.translation.xy(),
+                config.sprite_size,
+                behavior_color(behavior),
+            );
+        }
     }
+}
 
-    let new_vis = res.radius_papers;
-    let children = rock_query.iter().flatten();
-    let mut iter = query.iter_many_mut(children);
-    while let Some(mut vis) = iter.fetch_next() {
-        *vis = if new_vis {
-            Visibility::Visible
-        } else {
-            Visibility::Hidden
-        };
+fn toggle_energy(mut res: ResMut<DebugState>, keys: Res<ButtonInput<KeyCode>>) {
+    if keys.just_pressed(KeyCode::KeyN) {
+        res.energy = !res.energy;
     }
 }
 
-fn toggle_paper(
-    mut res: ResMut<DebugState>,
-    mut query: Query<&mut Visibility, With<Paper>>,
-    keys: Res<ButtonInput<KeyCode>>,
+// a short bar just above the sprite, green when full and red when empty
+fn draw_energy(
+    res: Res<DebugState>,
+    config: Res<SimConfig>,
+    query: Query<(&Transform, &Energy, &Visibility)>,
+    mut gizmos: Gizmos,
 ) {
-    if query.is_empty() {
+    if !res.energy {
         return;
     }
 
-    if !keys.pressed(KeyCode::ShiftLeft) && keys.just_pressed(KeyCode::Digit2) {
-        res.papers = !res.papers;
+    let half = config.sprite_size;
+    for (transform, energy, vis) in query.iter() {
+        if *vis == Visibility::Hidden {
+            continue;
+        }
+        let left = transform.translation.xy() + Vec2::new(-half, half + 4.);
+        let right = left + Vec2::X * 2. * half;
+        let filled = left.lerp(right, energy.0);
+        gizmos.line_2d(left, right, Color::srgb(0.2, 0.2, 0.2));
+        gizmos.line_2d(left, filled, Color::srgb(1. - energy.0, energy.0, 0.2));
     }
+}
 
-    let new_vis = res.papers;
-
-    for mut vis in query.iter_mut() {
-        *vis = if new_vis {
-            Visibility::Visible
-        } else {
-            Visibility::Hidden
-        };
+// Digit1..Digit9 pick a faction in declaration order
+pub(super) const FACTION_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+fn toggle(set: &mut HashSet<Faction>, faction: Faction) {
+    if !set.remove(&faction) {
+        set.insert(faction);
     }
 }
 
-fn toggle_scissors(
+fn toggle_factions(
     mut res: ResMut<DebugState>,
-    mut query: Query<&mut Visibility, With<Scissors>>,
+    factions: Res<Factions>,
+    state: Res<State<GameState>>,
+    mut query: Query<(&mut Visibility, &Faction)>,
     keys: Res<ButtonInput<KeyCode>>,
 ) {
     if query.is_empty() {
         return;
     }
 
-    if !keys.pressed(KeyCode::ShiftLeft) && keys.just_pressed(KeyCode::Digit3) {
-        res.scissors = !res.scissors;
+    // the editor picks factions with the same keys
+    if !keys.pressed(KeyCode::ShiftLeft) && *state.get() != GameState::Editor {
+        for (faction, key) in factions.iter().zip(FACTION_KEYS) {
+            if keys.just_pressed(key) {
+                toggle(&mut res.hidden, faction);
+            }
+        }
     }
 
-    let new_vis = res.scissors;
-
-    for mut vis in query.iter_mut() {
-        *vis = if new_vis {
-            Visibility::Visible
-        } else {
+    for (mut vis, faction) in query.iter_mut() {
+        *vis = if res.hidden.contains(faction) {
             Visibility::Hidden
+        } else {
+            Visibility::Visible
         };
     }
 }
 
-fn toggle_scissors_radius(
+fn toggle_factions_radius(
     mut res: ResMut<DebugState>,
-    mut query: Query<&mut Visibility>,
-    rock_query: Query<&Children, With<Scissors>>,
+    factions: Res<Factions>,
+    mut query: Query<&mut Visibility, (With<VisionRing>, Without<Faction>)>,
+    owners: Query<(&Children, &Faction)>,
     keys: Res<ButtonInput<KeyCode>>,
 ) {
     if query.is_empty() {
         return;
     }
 
-    if keys.pressed(KeyCode::ShiftLeft) && keys.just_pressed(KeyCode::Digit3) {
-        res.radius_scissors = !res.radius_scissors;
+    if keys.pressed(KeyCode::ShiftLeft) {
+        for (faction, key) in factions.iter().zip(FACTION_KEYS) {
+            if keys.just_pressed(key) {
+                toggle(&mut res.radius, faction);
+            }
+        }
     }
 
-    let new_vis = res.radius_scissors;
-    let children = rock_query.iter().flatten();
-    let mut iter = query.iter_many_mut(children);
-    while let Some(mut vis) = iter.fetch_next() {
-        *vis = if new_vis {
-            Visibility::Visible
-        } else {
-            Visibility::Hidden
-        };
+    for (children, faction) in owners.iter() {
+        let new_vis = res.radius.contains(faction);
+        let mut iter = query.iter_many_mut(children);
+        while let Some(mut vis) = iter.fetch_next() {
+            *vis = if new_vis {
+                Visibility::Visible
+            } else {
+                Visibility::Hidden
+            };
+        }
     }
 }
 
@@ -250,11 +324,40 @@ fn control_sound(mut res: ResMut<GameControl>, keys: Res<ButtonInput<KeyCode>>)
     }
 }
 
-fn control_time(
+const VOLUME_STEP: f64 = 0.1;
+
+fn control_volume(mut res: ResMut<GameControl>, keys: Res<ButtonInput<KeyCode>>) {
+    if keys.just_pressed(KeyCode::Minus) {
+        res.volume = (res.volume - VOLUME_STEP).max(0.);
+    }
+    if keys.just_pressed(KeyCode::Equal) {
+        res.volume = (res.volume + VOLUME_STEP).min(1.);
+    }
+    if keys.just_pressed(KeyCode::KeyP) {
+        res.panning = !res.panning;
+    }
+}
+
+fn control_restart(
+    state: Res<State<GameState>>,
+    mut next: ResMut<NextState<GameState>>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    if *state.get() == GameState::GameOver && keys.just_pressed(KeyCode::KeyR) {
+        next.set(GameState::LoadingRes);
+    }
+}
+
+pub(super) fn control_time(
     mut res: ResMut<GameControl>,
+    state: Res<State<GameState>>,
     mut next: ResMut<NextState<GameState>>,
     keys: Res<ButtonInput<KeyCode>>,
 ) {
+    if !matches!(state.get(), GameState::InGame | GameState::Paused) {
+        return;
+    }
+
     if keys.just_pressed(KeyCode::Space) {
         res.stop = !res.stop;
     }
@@ -264,3 +367,73 @@ fn control_time(
         next.set(GameState::InGame);
     }
 }
+
+fn control_speed(
+    mut res: ResMut<GameControl>,
+    mut time: ResMut<Time<Virtual>>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    let speeds = GameControl::SPEEDS;
+    let current = speeds.iter().position(|&s| s == res.speed).unwrap_or(2);
+
+    if keys.just_pressed(KeyCode::BracketLeft) {
+        res.speed = speeds[current.saturating_sub(1)];
+    }
+    if keys.just_pressed(KeyCode::BracketRight) {
+        res.speed = speeds[(current + 1).min(speeds.len() - 1)];
+    }
+    if res.speed != time.relative_speed_f64() {
+        time.set_relative_speed_f64(res.speed);
+    }
+}
+
+fn control_step(
+    mut res: ResMut<GameControl>,
+    state: Res<State<GameState>>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    if *state.get() == GameState::Paused && keys.just_pressed(KeyCode::Period) {
+        res.step = true;
+    }
+}
+
+fn control_skip(
+    mut res: ResMut<GameControl>,
+    state: Res<State<GameState>>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    if !matches!(state.get(), GameState::InGame | GameState::Paused) {
+        res.skip = false;
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::End) {
+        res.skip = !res.skip;
+        if res.skip {
+            res.stop = false;
+        }
+    }
+}
+
+// how long a frame may spend on skipped ticks before drawing again, so the
+// window keeps responding
+const SKIP_BUDGET: Duration = Duration::from_millis(250);
+
+// runs whole simulation ticks back to back until the match is over
+fn skip_to_end(world: &mut World) {
+    if !world.resource::<GameControl>().skip
+        || *world.resource::<State<GameState>>().get() != GameState::InGame
+    {
+        return;
+    }
+
+    let start = Instant::now();
+    while start.elapsed() < SKIP_BUDGET {
+        run_tick(world);
+
+        if game_over_pending(world.resource::<NextState<GameState>>()) {
+            world.resource_mut::<GameControl>().skip = false;
+            break;
+        }
+    }
+}
@@ -0,0 +1,263 @@
+#![allow(clippy::type_complexity)]
+
+use std::f32::consts::PI;
+
+use bevy::{ecs::system::SystemParam, prelude::*, window::PrimaryWindow};
+use bevy_rand::prelude::*;
+use rand::Rng;
+
+use crate::{
+    entities::{Faction, Id},
+    resources::{config::SimConfig, factions::Factions, replay::Replay, Arena, Editor, GameState},
+};
+
+use super::{
+    debug::{control_time, FACTION_KEYS},
+    game::spawn,
+    graphics::TickPositions,
+    replay::Recording,
+};
+
+/// Sets up scenarios by hand. `Tab` while paused switches to
+/// [`GameState::Editor`], where the simulation stays off and the mouse edits
+/// the match: left click spawns an entity of the selected faction (or drags
+/// the one under the cursor), right click deletes, `1`-`9` select a faction
+/// and `B` toggles a brush that paints or erases many entities at once.
+///
+/// Replays cannot be edited, as they must play the recorded match, and
+/// neither can recorded matches, as edits are not part of a recording.
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Editor>()
+            .add_systems(Startup, setup_status)
+            .add_systems(
+                Update,
+                (
+                    // or `control_time` would keep the match paused
+                    toggle_editor.after(control_time).run_if(
+                        not(resource_exists::<Replay>).and(not(resource_exists::<Recording>)),
+                    ),
+                    update_status,
+                ),
+            )
+            .add_systems(
+                Update,
+                (select_faction, toggle_brush, edit, draw_brush)
+                    .run_if(in_state(GameState::Editor)),
+            );
+    }
+}
+
+// radius of the area the brush paints or erases
+const BRUSH_RADIUS: f32 = 60.;
+
+fn toggle_editor(
+    state: Res<State<GameState>>,
+    mut next: ResMut<NextState<GameState>>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    if !keys.just_pressed(KeyCode::Tab) {
+        return;
+    }
+
+    match state.get() {
+        GameState::Paused => next.set(GameState::Editor),
+        GameState::Editor => next.set(GameState::Paused),
+        _ => {}
+    }
+}
+
+fn select_faction(
+    mut editor: ResMut<Editor>,
+    factions: Res<Factions>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    for (faction, key) in factions.iter().zip(FACTION_KEYS) {
+        if keys.just_pressed(key) {
+            editor.faction = faction;
+        }
+    }
+}
+
+fn toggle_brush(mut editor: ResMut<Editor>, keys: Res<ButtonInput<KeyCode>>) {
+    if keys.just_pressed(KeyCode::KeyB) {
+        editor.brush = !editor.brush;
+    }
+}
+
+#[derive(Component)]
+struct EditorStatus;
+
+#[derive(SystemParam)]
+struct Mouse<'w, 's> {
+    buttons: Res<'w, ButtonInput<MouseButton>>,
+    window: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+    camera: Query<'w, 's, (&'static Camera, &'static GlobalTransform)>,
+    arena: Res<'w, Arena>,
+}
+
+impl Mouse<'_, '_> {
+    /// Where the mouse points, in world coordinates and inside the arena.
+    fn position(&self) -> Option<Vec2> {
+        let cursor = self.window.get_single().ok()?.cursor_position()?;
+        let (camera, transform) = self.camera.get_single().ok()?;
+        let position = camera.viewport_to_world_2d(transform, cursor).ok()?;
+        let bounds = self.arena.bounds();
+        Some(position.clamp(-bounds, bounds))
+    }
+}
+
+fn edit(
+    editor: Res<Editor>,
+    config: Res<SimConfig>,
+    mouse: Mouse,
+    mut entities: Query<(Entity, &Id, &mut Transform, Option<&mut TickPositions>), With<Faction>>,
+    mut dragging: Local<Option<Entity>>,
+    mut rng: ResMut<GlobalEntropy<WyRand>>,
+    mut commands: Commands,
+) {
+    let buttons = &mouse.buttons;
+    if !buttons.pressed(MouseButton::Left) {
+        *dragging = None;
+    }
+    let Some(cursor) = mouse.position() else {
+        return;
+    };
+    let bounds = mouse.arena.bounds();
+    let touching = config.sprite_size * 2.;
+
+    let under_cursor = entities
+        .iter()
+        .map(|(entity, _, transform, _)| (entity, transform.translation.xy().distance(cursor)))
+        .filter(|&(_, distance)| distance <= config.sprite_size)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(entity, _)| entity);
+    // at most one entity is spawned per frame, after the newest one
+    let id = Id(entities
+        .iter()
+        .map(|(_, id, ..)| id.0 + 1)
+        .max()
+        .unwrap_or(0));
+
+    if let Some(entity) = *dragging {
+        if let Ok((_, _, mut transform, positions)) = entities.get_mut(entity) {
+            transform.translation.x = cursor.x;
+            transform.translation.y = cursor.y;
+            // or the fixed loop would put it back where it was
+            if let Some(mut positions) = positions {
+                positions.previous = cursor;
+                positions.current = cursor;
+            }
+        }
+        return;
+    }
+
+    if editor.brush {
+        if buttons.pressed(MouseButton::Left) {
+            let angle = rng.gen_range(0.0..(2.0 * PI));
+            let point = cursor + Vec2::from_angle(angle) * rng.gen_range(0.0..BRUSH_RADIUS);
+            let point = point.clamp(-bounds, bounds);
+            let crowded = entities
+                .iter()
+                .any(|(_, _, transform, _)| transform.translation.xy().distance(point) < touching);
+            if !crowded {
+                let radius = rng.gen_range(config.vision_min..config.vision_max);
+                let transform = Transform::from_xyz(point.x, point.y, 0.);
+                spawn(
+                    &mut commands,
+                    &config,
+                    id,
+                    editor.faction,
+                    transform,
+                    radius,
+                );
+            }
+        }
+        if buttons.pressed(MouseButton::Right) {
+            for (entity, _, transform, _) in entities.iter() {
+                if transform.translation.xy().distance(cursor) <= BRUSH_RADIUS {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+        }
+        return;
+    }
+
+    if buttons.just_pressed(MouseButton::Left) {
+        match under_cursor {
+            Some(entity) => *dragging = Some(entity),
+            None => {
+                let radius = rng.gen_range(config.vision_min..config.vision_max);
+                let transform = Transform::from_xyz(cursor.x, cursor.y, 0.);
+                spawn(
+                    &mut commands,
+                    &config,
+                    id,
+                    editor.faction,
+                    transform,
+                    radius,
+                );
+            }
+        }
+    }
+    if buttons.just_pressed(MouseButton::Right) {
+        if let Some(entity) = under_cursor {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn draw_brush(editor: Res<Editor>, mouse: Mouse, mut gizmos: Gizmos) {
+    if !editor.brush {
+        return;
+    }
+    if let Some(cursor) = mouse.position() {
+        gizmos.circle_2d(cursor, BRUSH_RADIUS, Color::srgb(0.2, 0.6, 1.));
+    }
+}
+
+fn setup_status(mut commands: Commands) {
+    commands.spawn((
+        EditorStatus,
+        Text::default(),
+        TextFont {
+            font_size: 14.,
+            ..Default::default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.),
+            right: Val::Px(8.),
+            padding: UiRect::all(Val::Px(6.)),
+            ..Default::default()
+        },
+        BackgroundColor(Color::srgba(0., 0., 0., 0.5)),
+        Visibility::Hidden,
+    ));
+}
+
+fn update_status(
+    editor: Res<Editor>,
+    factions: Res<Factions>,
+    state: Res<State<GameState>>,
+    mut status: Query<(&mut Text, &mut Visibility), With<EditorStatus>>,
+) {
+    let Ok((mut text, mut vis)) = status.get_single_mut() else {
+        return;
+    };
+
+    if *state.get() != GameState::Editor {
+        *vis = Visibility::Hidden;
+        return;
+    }
+
+    *vis = Visibility::Visible;
+    let tool = if editor.brush { "brush" } else { "single" };
+    text.0 = format!(
+        "editing: {} ({tool})\nTab to close",
+        factions.name(editor.faction)
+    );
+}
@@ -1,116 +1,163 @@
 #![allow(clippy::type_complexity)]
 
+use std::collections::HashSet;
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::time::Duration;
-use std::{f32::consts::PI, ops::Deref};
 
 use bevy::math::vec3;
-use bevy::prelude::ops::{cos, sin};
-use bevy::{math::vec2, prelude::*};
-use bevy_kira_audio::*;
+use bevy::{app::FixedMain, ecs::system::SystemParam, prelude::*};
 use bevy_rand::prelude::*;
 use bevy_spatial::kdtree::KDTree2;
-use bevy_spatial::{AutomaticUpdate, SpatialAccess, SpatialStructure};
+use bevy_spatial::{AutomaticUpdate, SpatialAccess, SpatialSet, SpatialStructure};
 use rand::Rng;
 
-use crate::constants::{SPEED_FACTOR, SPRITE_SIZE};
-use crate::entities::{Velocity, Vision};
-use crate::resources::CollidablePairs;
+use crate::entities::{
+    Behavior, Energy, Faction, FixedVision, Health, Id, Perception, Sighting, Velocity, Vision,
+};
+use crate::events::{ConversionEvent, GameOverEvent};
+use crate::resources::{
+    config::{ConversionMode, SimConfig},
+    factions::Factions,
+    layout::SpawnLayout,
+    obstacle::{closest_on_segment, Obstacles},
+    scenario::{Scenario, ScenarioEntity},
+    Arena, CollidablePairs, CsvExport, GameControl, MatchStats, PopulationHistory, ScenarioExport,
+};
 use crate::{
-    entities::{HasEnemy, HasSprite, HasTarget, Paper, Rock, Scissors},
     resources::{GameState, GenerableRegions},
     utils::generate_regions,
 };
 
-use super::debug::{DebugPlugin, DebugRadius};
-
+/// The simulation itself. It does not open a window nor load any asset, so it
+/// can be driven either by [`super::graphics::GraphicsPlugin`] or by
+/// [`super::headless::HeadlessPlugin`], which must be added before this one.
+///
+/// Factions come from the [`Factions`] resource (classic Rock, Paper &
+/// Scissors unless one is inserted beforehand). All of them share a single
+/// spatial tree and every system looks up who beats whom in it.
+///
+/// The match runs in `FixedUpdate` at [`SimConfig::tick_rate`], so it plays
+/// out the same whatever the render frame rate.
 pub struct GameplayPlugin;
 
 impl Plugin for GameplayPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                resolution: (1080. / 2., 1920. / 2.).into(),
-                title: "RPS - Simulation".to_owned(),
-                name: Some("RPS - Simulation".to_owned()),
-                ..Default::default()
-            }),
-            ..Default::default()
-        }))
-        .add_plugins(
-            AutomaticUpdate::<Rock>::new()
-                .with_spatial_ds(SpatialStructure::KDTree2)
-                .with_frequency(Duration::from_millis(1)),
-        )
-        .add_plugins(
-            AutomaticUpdate::<Paper>::new()
-                .with_spatial_ds(SpatialStructure::KDTree2)
-                .with_frequency(Duration::from_millis(1)),
-        )
-        .add_plugins(
-            AutomaticUpdate::<Scissors>::new()
+        app.init_resource::<SimConfig>();
+        let timestep = app.world().resource::<SimConfig>().timestep();
+
+        app.add_plugins(
+            AutomaticUpdate::<Faction>::new()
                 .with_spatial_ds(SpatialStructure::KDTree2)
+                .with_schedule(FixedUpdate)
                 .with_frequency(Duration::from_millis(1)),
         )
-        .add_plugins(DebugPlugin)
-        .add_plugins(AudioPlugin)
+        .insert_resource(Time::<Fixed>::from_duration(timestep))
         .init_state::<GameState>()
+        .enable_state_scoped_entities::<GameState>()
+        .init_resource::<Factions>()
+        .init_resource::<Arena>()
+        .init_resource::<GameControl>()
+        .add_event::<ConversionEvent>()
+        .add_event::<GameOverEvent>()
         .insert_resource(GenerableRegions::default())
         .insert_resource(CollidablePairs::default())
+        .init_resource::<Obstacles>()
+        .insert_resource(MatchStats::default())
+        .insert_resource(PopulationHistory::default())
+        .init_resource::<CsvExport>()
+        .init_resource::<ScenarioExport>()
         .add_systems(
-            Startup,
-            (setup, spawn_entities)
-                .chain()
-                .run_if(in_state(GameState::LoadingRes)),
-        )
-        .add_systems(
-            Update,
+            OnEnter(GameState::LoadingRes),
             (
-                handle_targets::<Rock>,
-                handle_enemies::<Rock>,
-                handle_targets::<Paper>,
-                handle_enemies::<Paper>,
-                handle_targets::<Scissors>,
-                handle_enemies::<Scissors>,
-                detect_collisions::<Rock>,
-                detect_collisions::<Paper>,
-                detect_collisions::<Scissors>,
-                update_positions,
+                setup_obstacles,
+                (setup, spawn_entities)
+                    .chain()
+                    .run_if(not(resource_exists::<Scenario>)),
+                spawn_scenario.run_if(resource_exists::<Scenario>),
+                collide_obstacles,
+                check_boundaries,
             )
-                .chain()
-                .run_if(in_state(GameState::InGame)),
+                .chain(),
+        )
+        .add_systems(
+            OnTransition {
+                exited: GameState::LoadingRes,
+                entered: GameState::InGame,
+            },
+            export_scenario.run_if(|export: Res<ScenarioExport>| export.on_start),
         )
         .add_systems(
-            PostUpdate,
+            OnEnter(GameState::GameOver),
+            export_population.run_if(|export: Res<CsvExport>| export.on_game_over),
+        )
+        .add_systems(OnExit(GameState::GameOver), cleanup_match)
+        .add_systems(
+            FixedUpdate,
             (
+                convert_targets,
+                update_health,
+                perceive,
+                choose_behavior,
+                update_energy,
+                steer,
+                detect_collisions,
+                update_positions,
+                resolve_collisions,
+                collide_obstacles,
+                // last, so no push can leave anyone outside the arena
                 check_boundaries,
-                resolve_collisions::<Rock>,
-                resolve_collisions::<Paper>,
-                resolve_collisions::<Scissors>,
                 cleanup_collisions,
+                track_stats,
+                record_population,
+                check_game_over,
             )
                 .chain()
-                .run_if(in_state(GameState::InGame)),
-        );
+                .after(SpatialSet)
+                .run_if(in_state(GameState::InGame).or(|control: Res<GameControl>| control.step)),
+        )
+        .add_systems(FixedPostUpdate, |mut control: ResMut<GameControl>| {
+            control.step = false;
+        });
     }
 }
 
 type KdTree<T> = KDTree2<T>;
 
-fn detect_collisions<T: Component>(
-    query: Query<(Entity, &Transform)>,
-    tree: Res<KdTree<T>>,
+/// Entities within `radius` of `pos`, closest first. Ties are broken by
+/// position so the order never depends on how the tree was built.
+fn neighbours(tree: &KdTree<Faction>, pos: Vec2, radius: f32) -> Vec<(Vec2, Entity)> {
+    let mut found: Vec<_> = tree
+        .within_distance(pos, radius)
+        .into_iter()
+        .filter_map(|(other_pos, other)| Some((other_pos, other?)))
+        .collect();
+    found.sort_by(|a, b| {
+        pos.distance_squared(a.0)
+            .total_cmp(&pos.distance_squared(b.0))
+            .then(a.0.x.total_cmp(&b.0.x))
+            .then(a.0.y.total_cmp(&b.0.y))
+    });
+    found
+}
+
+// only entities of the same faction push each other, the rest convert
+fn detect_collisions(
+    config: Res<SimConfig>,
+    query: Query<(Entity, &Id, &Transform, &Faction)>,
+    tree: Res<KdTree<Faction>>,
     mut collision_pairs: ResMut<CollidablePairs>,
 ) {
-    for (entity, transform) in query.iter() {
+    for (entity, _, transform, faction) in query.iter().sort::<&Id>() {
         let pos = transform.translation.xy();
 
-        let nearby = tree.within_distance(pos, SPRITE_SIZE * 2.);
-
-        for &(_, other_entity) in nearby.iter() {
-            if let Some(other_entity) = other_entity {
-                if entity != other_entity {
-                    collision_pairs.0.push((entity, other_entity));
-                }
+        for (_, other_entity) in neighbours(&tree, pos, config.sprite_size * 2.) {
+            let same_faction = query
+                .get(other_entity)
+                .is_ok_and(|(_, _, _, other)| other == faction);
+            if entity != other_entity && same_faction {
+                collision_pairs.0.push((entity, other_entity));
             }
         }
     }
@@ -120,11 +167,12 @@ fn cleanup_collisions(mut collisions: ResMut<CollidablePairs>) {
     collisions.0.clear();
 }
 
-fn resolve_collisions<T: Component>(
-    mut query: Query<&mut Transform, With<T>>,
+fn resolve_collisions(
+    config: Res<SimConfig>,
+    mut query: Query<&mut Transform, With<Faction>>,
     collision_pairs: Res<CollidablePairs>,
 ) {
-    let estimated_distance = SPRITE_SIZE * 2.;
+    let estimated_distance = config.sprite_size * 2.;
     if query.is_empty() {
         return;
     };
@@ -147,140 +195,535 @@ fn resolve_collisions<T: Component>(
     }
 }
 
-fn update_positions(
-    mut rng: ResMut<GlobalEntropy<WyRand>>,
+fn track_stats(
     time: Res<Time>,
-    mut query: Query<(&mut Transform, &Velocity)>,
+    mut conversions: EventReader<ConversionEvent>,
+    mut stats: ResMut<MatchStats>,
 ) {
-    if query.is_empty() {
+    stats.ticks += 1;
+    stats.elapsed += time.delta();
+    stats.conversions += conversions.read().count() as u32;
+}
+
+fn record_population(
+    factions: Res<Factions>,
+    stats: Res<MatchStats>,
+    query: Query<&Faction>,
+    mut history: ResMut<PopulationHistory>,
+) {
+    let mut counts = vec![0; factions.len()];
+    for faction in query.iter() {
+        counts[faction.0] += 1;
+    }
+    history.samples.push((stats.elapsed, counts));
+}
+
+pub fn export_population(
+    factions: Res<Factions>,
+    history: Res<PopulationHistory>,
+    export: Res<CsvExport>,
+) {
+    let result = File::create(&export.path).and_then(|file| {
+        let mut out = BufWriter::new(file);
+        history.write_csv(&factions, &mut out)?;
+        out.flush()
+    });
+    match result {
+        Ok(()) => println!("population written to {}", export.path.display()),
+        Err(e) => eprintln!("cannot write {}: {e}", export.path.display()),
+    }
+}
+
+/// Whether the match already ended but the state has not changed yet. Other
+/// pending states (e.g. the pause key setting `InGame` every frame) do not
+/// stop the simulation.
+pub fn game_over_pending(next: &NextState<GameState>) -> bool {
+    matches!(next, NextState::Pending(GameState::GameOver))
+}
+
+fn check_game_over(
+    stats: Res<MatchStats>,
+    query: Query<&Faction>,
+    mut next: ResMut<NextState<GameState>>,
+    mut game_over: EventWriter<GameOverEvent>,
+) {
+    // several ticks can run before the state actually changes
+    if game_over_pending(&next) {
         return;
     }
 
-    for (mut transform, velocity) in query.iter_mut() {
-        let tremor_x = rng.gen_range(-1.5..1.5) * 0.5;
-        let tremor_y = rng.gen_range(-1.5..1.5) * 0.5;
-        let pos = transform.translation.xy() + velocity.0 * time.delta_secs();
-        transform.translation = vec3(pos.x + tremor_x, pos.y + tremor_y, 0.0);
+    let mut alive = query.iter();
+    let Some(&winner) = alive.next() else {
+        return;
+    };
+
+    if alive.all(|&faction| faction == winner) {
+        next.set(GameState::GameOver);
+        game_over.send(GameOverEvent {
+            winner,
+            elapsed: stats.elapsed,
+            conversions: stats.conversions,
+        });
     }
 }
 
-fn handle_targets<T: Component + HasTarget + HasSprite + Copy>(
+/// Runs a single simulation tick right away instead of waiting for the fixed
+/// loop, e.g. to fast-forward without rendering.
+pub fn run_tick(world: &mut World) {
+    let timestep = world.resource::<Time<Fixed>>().timestep();
+    world.resource_mut::<Time<Fixed>>().advance_by(timestep);
+    let fixed = world.resource::<Time<Fixed>>().as_generic();
+    *world.resource_mut::<Time>() = fixed;
+
+    world.run_schedule(FixedMain);
+
+    let virt = world.resource::<Time<Virtual>>().as_generic();
+    *world.resource_mut::<Time>() = virt;
+}
+
+// wipes the finished match so `LoadingRes` can spawn a fresh one
+pub fn cleanup_match(
     mut commands: Commands,
-    server: Res<AssetServer>,
-    audio: Res<Audio>,
-    mut query: Query<(&mut Transform, &T)>,
-    mut targets: Query<Entity, (With<T::Target>, Without<T>)>,
-    tree: Res<KdTree<T::Target>>,
+    query: Query<Entity, With<Velocity>>,
+    mut stats: ResMut<MatchStats>,
+    mut history: ResMut<PopulationHistory>,
 ) {
-    if query.is_empty() {
-        return;
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    *stats = MatchStats::default();
+    history.samples.clear();
+}
+
+// the only place where positions move, everything else steers `Velocity`
+fn update_positions(time: Res<Time>, mut query: Query<(&mut Transform, &Velocity)>) {
+    for (mut transform, velocity) in query.iter_mut() {
+        let step = velocity.0 * time.delta_secs();
+        transform.translation += vec3(step.x, step.y, 0.0);
     }
+}
+
+// how long an entity shows it was hit
+const HIT_FLASH_SECS: f32 = 0.15;
+
+/// Targets close enough to be converted.
+#[derive(SystemParam)]
+struct Contacts<'w, 's> {
+    config: Res<'w, SimConfig>,
+    factions: Res<'w, Factions>,
+    tree: Res<'w, KdTree<Faction>>,
+    members: Query<'w, 's, &'static Faction>,
+}
 
-    for (mut transform, me) in query.iter_mut() {
+impl Contacts<'_, '_> {
+    /// Closest target of `me` touching `pos` that `allowed` accepts.
+    fn target(&self, pos: Vec2, me: Faction, allowed: impl Fn(Entity) -> bool) -> Option<Entity> {
+        let contact = self.config.sprite_size * 2.;
+        let targets = self.factions.targets(me);
+        neighbours(&self.tree, pos, contact)
+            .into_iter()
+            .map(|(_, other)| other)
+            .find(|&other| {
+                self.members.get(other).is_ok_and(|f| targets.contains(f)) && allowed(other)
+            })
+    }
+}
+
+// touching a target converts it, or with `ConversionMode::Health` hurts it
+// until it gives in, unless still resting from the last one
+fn convert_targets(
+    mut commands: Commands,
+    mut conversions: EventWriter<ConversionEvent>,
+    time: Res<Time>,
+    query: Query<(Entity, &Id, &Transform, &Faction, &Behavior)>,
+    mut healths: Query<&mut Health>,
+    contacts: Contacts,
+) {
+    let config = &contacts.config;
+    let damage = config.contact_damage * time.delta_secs();
+    // sides only change once commands apply, until then the query still
+    // shows the converted ones on their old side
+    let mut converted = HashSet::new();
+
+    for (actor, _, transform, &me, behavior) in query.iter().sort::<&Id>() {
+        if matches!(behavior, Behavior::Rest { .. }) || converted.contains(&actor) {
+            continue;
+        }
         let pos = transform.translation.xy();
+        // someone who just changed sides cannot be hurt, so go for another
+        let vulnerable = |target| match config.conversion {
+            ConversionMode::Instant => true,
+            ConversionMode::Health => healths
+                .get(target)
+                .is_ok_and(|health| health.invulnerable <= 0.),
+        };
+        let Some(target) = contacts.target(pos, me, |target| {
+            !converted.contains(&target) && vulnerable(target)
+        }) else {
+            continue;
+        };
 
-        if let Some((target_pos, Some(target))) = tree.nearest_neighbour(pos) {
-            if targets.is_empty() {
+        if config.conversion == ConversionMode::Health {
+            let Ok(mut health) = healths.get_mut(target) else {
+                continue;
+            };
+            health.points -= damage;
+            health.flash = HIT_FLASH_SECS;
+            if health.points > 0. {
                 continue;
             }
-            let dis = pos.distance(target_pos);
-
-            if dis <= SPRITE_SIZE * 2. {
-                let mut sprite = Sprite::from_image(server.load(me.img()));
-                sprite.custom_size = Some(Vec2::splat(SPRITE_SIZE * 2.));
-                let sound = server.load(me.sound());
-                audio.play(sound);
-
-                if let Ok(target) = targets.get_mut(target) {
-                    commands
-                        .entity(target)
-                        .remove::<(T::Target, Sprite)>()
-                        .insert((*me, sprite));
-                }
-            } else {
-                let towards = (target_pos - pos).normalize() * SPEED_FACTOR;
-                transform.translation += vec3(towards.x, towards.y, 0.0);
-            }
+            *health = Health {
+                invulnerable: config.invulnerable_secs,
+                ..Health::new(config.max_health)
+            };
         }
+
+        converted.insert(target);
+        // what the target saw belongs to its old side
+        commands
+            .entity(target)
+            .insert((me, Perception::default(), Behavior::default()));
+        commands.entity(actor).insert(Behavior::Rest {
+            left: config.rest_secs,
+        });
+        conversions.send(ConversionEvent {
+            actor,
+            target,
+            faction: me,
+        });
     }
 }
 
-fn handle_enemies<T: Component + HasEnemy>(
-    mut query: Query<(&mut Transform, &Vision), With<T>>,
-    tree: Res<KdTree<T::Enemy>>,
+// invulnerability and the hit tint wear off
+fn update_health(time: Res<Time>, mut query: Query<&mut Health>) {
+    let delta = time.delta_secs();
+
+    for mut health in query.iter_mut() {
+        if health.invulnerable > 0. || health.flash > 0. {
+            health.invulnerable = (health.invulnerable - delta).max(0.);
+            health.flash = (health.flash - delta).max(0.);
+        }
+    }
+}
+
+/// What an entity can make out around it: only what lies in the cone ahead
+/// of it, and nothing hidden behind obstacles (or other entities, with
+/// `SimConfig::occlusion`).
+#[derive(SystemParam)]
+struct Sight<'w, 's> {
+    config: Res<'w, SimConfig>,
+    tree: Res<'w, KdTree<Faction>>,
+    members: Query<'w, 's, &'static Faction>,
+    obstacles: Res<'w, Obstacles>,
+}
+
+impl Sight<'_, '_> {
+    /// Whether `viewer`, at `pos` and moving along `heading`, sees `other`.
+    fn sees(
+        &self,
+        viewer: Entity,
+        pos: Vec2,
+        heading: Option<Vec2>,
+        other: Entity,
+        other_pos: Vec2,
+    ) -> bool {
+        let half_fov = self.config.fov.to_radians() / 2.;
+        let in_cone = match heading {
+            Some(heading) if half_fov < PI => heading.angle_to(other_pos - pos).abs() <= half_fov,
+            // standing still, it looks all around
+            _ => true,
+        };
+        if !in_cone || !self.obstacles.in_sight(pos, other_pos) {
+            return false;
+        }
+        if !self.config.occlusion {
+            return true;
+        }
+
+        let size = self.config.sprite_size;
+        let middle = (pos + other_pos) / 2.;
+        let reach = pos.distance(other_pos) / 2. + size;
+        !neighbours(&self.tree, middle, reach)
+            .into_iter()
+            .any(|(between_pos, between)| {
+                between != viewer
+                    && between != other
+                    && closest_on_segment(between_pos, pos, other_pos).distance(between_pos) < size
+            })
+    }
+
+    /// Closest entity `viewer` sees within `radius` that belongs to one of
+    /// `factions`.
+    fn nearest_of(
+        &self,
+        viewer: Entity,
+        pos: Vec2,
+        heading: Option<Vec2>,
+        radius: f32,
+        factions: &[Faction],
+    ) -> Option<Vec2> {
+        neighbours(&self.tree, pos, radius)
+            .into_iter()
+            .filter(|&(_, other)| self.members.get(other).is_ok_and(|f| factions.contains(f)))
+            .find(|&(other_pos, other)| self.sees(viewer, pos, heading, other, other_pos))
+            .map(|(other_pos, _)| other_pos)
+    }
+}
+
+/// Updates a sighting: refreshed when seen again, otherwise aged and
+/// eventually forgotten, or dropped once reached with nobody there.
+fn remember(
+    previous: Option<Sighting>,
+    seen: Option<Vec2>,
+    pos: Vec2,
+    reached: f32,
+    delta: f32,
+    memory_secs: f32,
+) -> Option<Sighting> {
+    if let Some(position) = seen {
+        return Some(Sighting { position, age: 0. });
+    }
+
+    previous
+        .map(|sighting| Sighting {
+            age: sighting.age + delta,
+            ..sighting
+        })
+        .filter(|sighting| sighting.age < memory_secs && sighting.position.distance(pos) > reached)
+}
+
+// looks around for the closest target and enemy in sight
+fn perceive(
+    factions: Res<Factions>,
+    time: Res<Time>,
+    mut query: Query<(
+        Entity,
+        &Transform,
+        &Velocity,
+        &Vision,
+        &Faction,
+        &mut Perception,
+    )>,
+    sight: Sight,
 ) {
-    for (mut transform, vision) in query.iter_mut() {
+    let config = &sight.config;
+    let delta = time.delta_secs();
+
+    for (entity, transform, velocity, vision, &me, mut perception) in query.iter_mut() {
         let pos = transform.translation.xy();
+        let heading = velocity.0.try_normalize();
+        let target = sight.nearest_of(entity, pos, heading, vision.0, factions.targets(me));
+        let enemy = sight.nearest_of(entity, pos, heading, vision.0, factions.enemies(me));
 
-        let within_distance = tree.within_distance(pos, vision.0);
-        let nearest_enemy = within_distance.iter().reduce(|acc, e| {
-            let closest = (acc.0 - pos).length_squared();
-            let current = (e.0 - pos).length_squared();
-            if closest < current {
-                acc
+        let reached = config.sprite_size;
+        perception.target = remember(
+            perception.target,
+            target,
+            pos,
+            reached,
+            delta,
+            config.memory_secs,
+        );
+        perception.enemy = remember(
+            perception.enemy,
+            enemy,
+            pos,
+            reached,
+            delta,
+            config.memory_secs,
+        );
+    }
+}
+
+/// Direction away from the allies closer than `radius`, stronger the closer
+/// they are, at most of length 1.
+fn separation(
+    tree: &KdTree<Faction>,
+    members: &Query<&Faction>,
+    pos: Vec2,
+    me: Faction,
+    radius: f32,
+) -> Vec2 {
+    neighbours(tree, pos, radius)
+        .into_iter()
+        .filter(|&(_, other)| members.get(other).is_ok_and(|&f| f == me))
+        .map(|(other_pos, _)| {
+            let away = pos - other_pos;
+            let distance = away.length();
+            if distance > 0. {
+                away / distance * (1. - distance / radius)
             } else {
-                e
+                Vec2::ZERO
             }
-        });
+        })
+        .sum::<Vec2>()
+        .clamp_length_max(1.)
+}
 
-        if let Some(&(enemy_pos, _)) = nearest_enemy {
-            let push = (enemy_pos - pos).normalize() * -2.;
+// fleeing wins over chasing when the enemy is the closer one, and an enemy
+// in sight cuts a rest short
+fn choose_behavior(time: Res<Time>, mut query: Query<(&Transform, &Perception, &mut Behavior)>) {
+    let delta = time.delta_secs();
 
-            let max_speed = 2.;
-            let push_magnitude = push.length();
+    for (transform, perception, mut behavior) in query.iter_mut() {
+        let pos = transform.translation.xy();
+        let next = match (*behavior, perception.target, perception.enemy) {
+            (Behavior::Rest { left }, _, None) if left > delta => {
+                Behavior::Rest { left: left - delta }
+            }
+            (_, Some(target), Some(enemy))
+                if target.position.distance(pos) < enemy.position.distance(pos) =>
+            {
+                Behavior::Chase
+            }
+            (_, _, Some(_)) => Behavior::Flee,
+            (_, Some(_), None) => Behavior::Chase,
+            (_, None, None) => Behavior::Wander,
+        };
+        behavior.set_if_neq(next);
+    }
+}
 
-            let final_push = if push_magnitude > max_speed {
-                push * (max_speed / push_magnitude)
-            } else {
-                push
-            };
+// chasing and fleeing wear entities out, the rest of the time they recover
+fn update_energy(
+    config: Res<SimConfig>,
+    time: Res<Time>,
+    mut query: Query<(&Behavior, &mut Energy)>,
+) {
+    let delta = time.delta_secs();
 
-            let lerp_factor = 0.5; // Ajusta el factor de interpolación (0.0 - 1.0)
-            let smoothed_push = transform
-                .translation
-                .xy()
-                .lerp(transform.translation.truncate() + final_push, lerp_factor)
-                - transform.translation.truncate();
+    for (behavior, mut energy) in query.iter_mut() {
+        let change = match behavior {
+            Behavior::Chase | Behavior::Flee => -config.energy_drain,
+            Behavior::Wander | Behavior::Rest { .. } => config.energy_regain,
+        };
+        energy.0 = (energy.0 + change * delta).clamp(0., 1.);
+    }
+}
 
-            transform.translation += vec3(smoothed_push.x, smoothed_push.y, 0.0);
-        }
+// the current behaviour asks for a velocity, plus some room from allies and
+// a little drift; `max_accel` limits how fast the entity gets there
+fn steer(
+    config: Res<SimConfig>,
+    time: Res<Time>,
+    mut rng: ResMut<GlobalEntropy<WyRand>>,
+    mut query: Query<(
+        &Id,
+        &Transform,
+        &Faction,
+        &Perception,
+        &Behavior,
+        &Energy,
+        &mut Velocity,
+    )>,
+    members: Query<&Faction>,
+    tree: Res<KdTree<Faction>>,
+) {
+    let max_change = config.max_accel * time.delta_secs();
+    let max_turn = config.wander_turn.to_radians() * time.delta_secs();
+
+    for (_, transform, &me, perception, behavior, energy, mut velocity) in
+        query.iter_mut().sort::<&Id>()
+    {
+        let pos = transform.translation.xy();
+        let mut desired = Vec2::ZERO;
+
+        // keep roughly the current heading, or pick one when standing still
+        let heading = match velocity.0.try_normalize() {
+            Some(direction) => direction.to_angle() + rng.gen_range(-max_turn..=max_turn),
+            None => rng.gen_range(0.0..(2.0 * PI)),
+        };
+        let wander = Vec2::from_angle(heading);
+
+        // what was seen a while ago matters less than what is in sight
+        let speed = match (*behavior, perception.target, perception.enemy) {
+            (Behavior::Chase, Some(target), _) => {
+                let certainty = target.certainty(config.memory_secs);
+                desired +=
+                    (target.position - pos).normalize_or_zero() * config.seek_weight * certainty;
+                desired += wander * config.wander_weight;
+                config.chase_speed
+            }
+            (Behavior::Flee, _, Some(enemy)) => {
+                let certainty = enemy.certainty(config.memory_secs);
+                desired +=
+                    (pos - enemy.position).normalize_or_zero() * config.flee_weight * certainty;
+                desired += wander * config.wander_weight;
+                config.flee_speed
+            }
+            (Behavior::Rest { .. }, ..) => config.rest_speed,
+            // with nobody to chase, roam around to find someone
+            _ => {
+                desired += wander * config.explore_weight;
+                config.wander_speed
+            }
+        };
+
+        let crowd = config.sprite_size * 3.;
+        desired += separation(&tree, &members, pos, me, crowd) * config.separation_weight;
+
+        // exhausted entities are easy prey
+        let stamina = config.exhausted_speed + (1. - config.exhausted_speed) * energy.0;
+        let max_speed = config.max_speed * speed * stamina;
+        let desired = (desired * config.max_speed).clamp_length_max(max_speed);
+        let change = (desired - velocity.0).clamp_length_max(max_change);
+        velocity.0 = (velocity.0 + change).clamp_length_max(config.max_speed);
     }
 }
 
-fn check_boundaries(window: Query<&Window>, mut query: Query<(&mut Transform, &mut Velocity)>) {
-    if window.is_empty() || query.is_empty() {
+// obstacles push entities back out, which then slide along them
+fn collide_obstacles(
+    config: Res<SimConfig>,
+    obstacles: Res<Obstacles>,
+    mut query: Query<(&mut Transform, &mut Velocity)>,
+) {
+    if obstacles.0.is_empty() {
         return;
     }
 
-    let window = window.get_single().unwrap();
-    let width = (window.width() - 38.) / 2.;
-    let height = (window.height() - 38.) / 2.;
-    let gap = 5.0; // Adjust this value for the desired gap size
+    for (mut transform, mut velocity) in query.iter_mut() {
+        let (pos, pushed) = obstacles.push_out(transform.translation.xy(), config.sprite_size);
+        let Some(normal) = pushed.try_normalize() else {
+            continue;
+        };
+        transform.translation.x = pos.x;
+        transform.translation.y = pos.y;
+        let inward = velocity.0.dot(normal).min(0.);
+        velocity.0 -= normal * inward;
+    }
+}
+
+fn check_boundaries(arena: Res<Arena>, mut query: Query<(&mut Transform, &mut Velocity)>) {
+    if query.is_empty() {
+        return;
+    }
+
+    let bounds = arena.bounds();
 
     for (mut entity, mut velocity) in query.iter_mut() {
         let mut pos = entity.translation.xy();
 
         // Check for left boundary
-        if pos.x <= -width + gap {
-            pos.x = -width + gap;
+        if pos.x <= -bounds.x {
+            pos.x = -bounds.x;
             velocity.0.x = velocity.0.x.abs(); // Ensure positive velocity to move away from the boundary
         }
 
         // Check for right boundary
-        if pos.x >= width - gap {
-            pos.x = width - gap;
+        if pos.x >= bounds.x {
+            pos.x = bounds.x;
             velocity.0.x = -velocity.0.x.abs(); // Ensure negative velocity to move away from the boundary
         }
 
         // Check for bottom boundary
-        if pos.y <= -height + gap {
-            pos.y = -height + gap;
+        if pos.y <= -bounds.y {
+            pos.y = -bounds.y;
             velocity.0.y = velocity.0.y.abs();
         }
 
         // Check for top boundary
-        if pos.y >= height - gap {
-            pos.y = height - gap;
+        if pos.y >= bounds.y {
+            pos.y = bounds.y;
             velocity.0.y = -velocity.0.y.abs();
         }
 
@@ -290,99 +733,352 @@ fn check_boundaries(window: Query<&Window>, mut query: Query<(&mut Transform, &m
     }
 }
 
-fn setup(mut regions: ResMut<GenerableRegions>, query: Query<&Window>) {
-    let window = query.single();
-    let width = window.resolution.width() / 2.;
-    let height = window.resolution.height() / 2.;
-    let generated_regions = generate_regions(width, height, 48);
-    regions.0 = generated_regions;
+fn setup_obstacles(
+    config: Res<SimConfig>,
+    scenario: Option<Res<Scenario>>,
+    mut obstacles: ResMut<Obstacles>,
+) {
+    obstacles.0 = config.obstacles.clone();
+    if let Some(scenario) = scenario {
+        obstacles.0.extend(scenario.obstacles.iter().cloned());
+    }
+}
+
+fn setup(
+    config: Res<SimConfig>,
+    factions: Res<Factions>,
+    mut regions: ResMut<GenerableRegions>,
+    mut rng: ResMut<GlobalEntropy<WyRand>>,
+    arena: Res<Arena>,
+) {
+    if config.layout != SpawnLayout::Regions {
+        regions.0.clear();
+        return;
+    }
+
+    regions.0 = generate_regions(
+        arena.bounds(),
+        config.regions,
+        config.region_radius,
+        config.sprite_size * 2.,
+        factions.len(),
+        &mut *rng,
+    );
 }
 
 fn spawn_entities(
+    config: Res<SimConfig>,
+    factions: Res<Factions>,
     regions: Res<GenerableRegions>,
-    server: Res<AssetServer>,
+    arena: Res<Arena>,
     mut next: ResMut<NextState<GameState>>,
     mut rng: ResMut<GlobalEntropy<WyRand>>,
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    let len = 3;
-    let regions = regions.0.deref().iter().enumerate();
-
-    let material = MeshMaterial2d(materials.add(Color::linear_rgb(255., 0., 0.)));
-
-    for (i, &(x, y, r)) in regions {
-        std::iter::repeat_n((), 2).for_each(|_| {
-            let angle = rng.gen_range(0.0..(2.0 * PI));
-            let pos = vec2(x, y) + vec2(cos(angle), sin(angle)) * rng.gen_range(0.0..r);
-            let transform = Transform::from_xyz(pos.x, pos.y, 0.0);
-            let radius = rng.gen_range(75.0..125.0);
-            match i % len {
-                0 => spawn(
-                    &mut commands,
-                    Rock,
-                    transform,
-                    radius,
-                    &server,
-                    &mut meshes,
-                    material.clone(),
-                ),
-                1 => spawn(
-                    &mut commands,
-                    Paper,
-                    transform,
-                    radius,
-                    &server,
-                    &mut meshes,
-                    material.clone(),
-                ),
-                2 => spawn(
-                    &mut commands,
-                    Scissors,
-                    transform,
-                    radius,
-                    &server,
-                    &mut meshes,
-                    material.clone(),
-                ),
-                _ => {}
-            };
-        });
+    let counts = config.faction_counts(factions.len());
+    let points = config.layout.points(
+        &counts,
+        &regions.0,
+        arena.bounds(),
+        config.sprite_size * 2.,
+        &mut *rng,
+    );
+
+    for (id, (faction, pos)) in (0..).map(Id).zip(points) {
+        let transform = Transform::from_xyz(pos.x, pos.y, 0.0);
+        let radius = rng.gen_range(config.vision_min..config.vision_max);
+        spawn(&mut commands, &config, id, faction, transform, radius);
     }
 
     next.set(GameState::InGame);
 }
 
-fn spawn<T: Component + HasSprite>(
+fn spawn_scenario(
+    config: Res<SimConfig>,
+    factions: Res<Factions>,
+    scenario: Res<Scenario>,
+    mut next: ResMut<NextState<GameState>>,
+    mut commands: Commands,
+) {
+    for (id, entity) in (0..).map(Id).zip(scenario.entities.iter()) {
+        // checked at startup
+        let Some(faction) = factions.find(&entity.faction) else {
+            continue;
+        };
+        let (x, y) = entity.position;
+        let transform = Transform::from_xyz(x, y, 0.0);
+        let spawned = spawn(&mut commands, &config, id, faction, transform, 0.);
+        commands.entity(spawned).insert((
+            Vision(entity.vision),
+            FixedVision,
+            Velocity(Vec2::from(entity.velocity)),
+        ));
+    }
+
+    next.set(GameState::InGame);
+}
+
+pub(super) fn spawn(
     commands: &mut Commands,
-    entity: T,
+    config: &SimConfig,
+    id: Id,
+    faction: Faction,
     transform: Transform,
     radius: f32,
-    server: &Res<AssetServer>,
-    meshes: &mut ResMut<Assets<Mesh>>,
-    material: MeshMaterial2d<ColorMaterial>,
-) {
-    let mut sprite = Sprite::from_image(server.load(entity.img()));
-    sprite.custom_size = Some(Vec2::splat(SPRITE_SIZE * 2.));
-    let velocity = Velocity(Vec2::splat(SPEED_FACTOR));
-    let r = SPRITE_SIZE + radius;
-    let vision = Vision(r);
-    let mesh = meshes.add(Annulus::new(r - 1., r + 1.));
+) -> Entity {
+    let vision = Vision(config.sprite_size + radius);
     commands
         .spawn((
-            entity,
-            sprite,
+            id,
+            faction,
             transform,
-            vision.clone(),
-            velocity,
+            vision,
+            Velocity(Vec2::ZERO),
+            Perception::default(),
+            Behavior::default(),
+            Energy::default(),
+            Health::new(config.max_health),
             Visibility::Visible,
         ))
-        .with_children(|c| {
-            c.spawn(DebugRadius {
-                mesh: Mesh2d(mesh),
-                material,
-                visible: Visibility::Hidden,
-            });
-        });
+        .id()
+}
+
+/// Saves every entity, in `Id` order, as a [`Scenario`].
+pub fn export_scenario(
+    factions: Res<Factions>,
+    export: Res<ScenarioExport>,
+    scenario: Option<Res<Scenario>>,
+    query: Query<(&Id, &Faction, &Transform, &Vision, &Velocity)>,
+) {
+    let entities = query
+        .iter()
+        .sort::<&Id>()
+        .map(
+            |(_, &faction, transform, vision, velocity)| ScenarioEntity {
+                faction: factions.name(faction).to_owned(),
+                position: (transform.translation.x, transform.translation.y),
+                vision: vision.0,
+                velocity: (velocity.0.x, velocity.0.y),
+            },
+        )
+        .collect();
+
+    // obstacles of the config are not the scenario's to keep
+    let obstacles = scenario.map_or_else(Vec::new, |s| s.obstacles.clone());
+    match Scenario::new(entities, obstacles).save(&export.path) {
+        Ok(()) => println!("scenario written to {}", export.path.display()),
+        Err(e) => eprintln!("cannot write {}: {e}", export.path.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{ecs::system::RunSystemOnce, math::vec2};
+
+    use super::*;
+    use crate::{plugins::headless::match_app, resources::obstacle::Obstacle};
+
+    const DELTA: f32 = 0.5;
+    const REACHED: f32 = 10.;
+
+    fn sighting(x: f32, age: f32) -> Option<Sighting> {
+        Some(Sighting {
+            position: vec2(x, 0.),
+            age,
+        })
+    }
+
+    #[test]
+    fn sightings_refresh_when_seen_again() {
+        let seen = remember(
+            sighting(100., 1.5),
+            Some(vec2(50., 0.)),
+            Vec2::ZERO,
+            REACHED,
+            DELTA,
+            2.,
+        );
+        assert_eq!(seen, sighting(50., 0.));
+        assert_eq!(seen.unwrap().certainty(2.), 1.);
+    }
+
+    #[test]
+    fn sightings_age_until_forgotten() {
+        let mut memory = sighting(100., 0.);
+        let mut certainty = 1.;
+        for _ in 0..3 {
+            memory = remember(memory, None, Vec2::ZERO, REACHED, DELTA, 2.);
+            let faded = memory.unwrap().certainty(2.);
+            assert!(faded < certainty);
+            certainty = faded;
+        }
+        assert_eq!(memory, sighting(100., 1.5));
+        assert_eq!(remember(memory, None, Vec2::ZERO, REACHED, DELTA, 2.), None);
+    }
+
+    #[test]
+    fn sightings_drop_once_reached() {
+        let memory = sighting(100., 0.);
+        assert!(remember(memory, None, vec2(85., 0.), REACHED, DELTA, 2.).is_some());
+        assert_eq!(
+            remember(memory, None, vec2(95., 0.), REACHED, DELTA, 2.),
+            None
+        );
+    }
+
+    #[test]
+    fn no_memory_keeps_only_what_is_in_sight() {
+        let seen = remember(None, Some(vec2(100., 0.)), Vec2::ZERO, REACHED, DELTA, 0.);
+        assert_eq!(seen.unwrap().certainty(0.), 1.);
+        assert_eq!(remember(seen, None, Vec2::ZERO, REACHED, DELTA, 0.), None);
+        assert_eq!(sighting(100., DELTA).unwrap().certainty(0.), 0.);
+    }
+
+    // what an entity at the origin ends up doing after one tick
+    fn choose(behavior: Behavior, target: Option<f32>, enemy: Option<f32>) -> Behavior {
+        let mut world = World::new();
+        let mut time = Time::<()>::default();
+        time.advance_by(Duration::from_secs_f32(DELTA));
+        world.insert_resource(time);
+        let perception = Perception {
+            target: target.and_then(|x| sighting(x, 0.)),
+            enemy: enemy.and_then(|x| sighting(x, 0.)),
+        };
+        let entity = world
+            .spawn((Transform::default(), perception, behavior))
+            .id();
+
+        world.run_system_once(choose_behavior).unwrap();
+        *world.get::<Behavior>(entity).unwrap()
+    }
+
+    #[test]
+    fn closer_enemies_win_over_targets() {
+        assert_eq!(
+            choose(Behavior::Chase, Some(100.), Some(50.)),
+            Behavior::Flee
+        );
+        assert_eq!(
+            choose(Behavior::Flee, Some(50.), Some(100.)),
+            Behavior::Chase
+        );
+        assert_eq!(choose(Behavior::Wander, None, Some(100.)), Behavior::Flee);
+        assert_eq!(choose(Behavior::Chase, None, None), Behavior::Wander);
+    }
+
+    #[test]
+    fn rest_counts_down() {
+        let rest = Behavior::Rest { left: 2. };
+        assert_eq!(choose(rest, None, None), Behavior::Rest { left: 1.5 });
+        // targets can wait
+        assert_eq!(choose(rest, Some(50.), None), Behavior::Rest { left: 1.5 });
+        let over = Behavior::Rest { left: 0.3 };
+        assert_eq!(choose(over, Some(50.), None), Behavior::Chase);
+        assert_eq!(choose(over, None, None), Behavior::Wander);
+    }
+
+    #[test]
+    fn enemies_cut_rest_short() {
+        let rest = Behavior::Rest { left: 2. };
+        assert_eq!(choose(rest, None, Some(100.)), Behavior::Flee);
+        assert_eq!(choose(rest, Some(50.), Some(100.)), Behavior::Chase);
+    }
+
+    fn scenario_app(config: SimConfig, entities: &[(&str, f32)], obstacles: Vec<Obstacle>) -> App {
+        let entities = entities
+            .iter()
+            .map(|&(faction, x)| ScenarioEntity {
+                faction: faction.to_owned(),
+                position: (x, 0.),
+                vision: 100.,
+                velocity: (0., 0.),
+            })
+            .collect();
+        let mut app = match_app(1, config);
+        app.insert_resource(Scenario::new(entities, obstacles));
+        app.update();
+        app
+    }
+
+    fn health_app(entities: &[(&str, f32)]) -> App {
+        // too slow to move apart
+        let config = SimConfig {
+            conversion: ConversionMode::Health,
+            max_speed: 0.001,
+            ..Default::default()
+        };
+        scenario_app(config, entities, Vec::new())
+    }
+
+    fn find(app: &mut App, id: Id) -> (Faction, Health) {
+        let world = app.world_mut();
+        world
+            .query::<(&Id, &Faction, &Health)>()
+            .iter(world)
+            .find(|(&other, ..)| other == id)
+            .map(|(_, &faction, &health)| (faction, health))
+            .unwrap()
+    }
+
+    #[test]
+    fn health_converts_only_at_zero() {
+        let mut app = health_app(&[("Rock", 0.), ("Scissors", 20.)]);
+        let max_health = SimConfig::default().max_health;
+        let mut last = max_health;
+
+        for _ in 0..100 {
+            app.update();
+            let (faction, health) = find(&mut app, Id(1));
+            if faction == Faction(0) {
+                // a fresh start, out of reach for a while
+                assert_eq!(health.points, max_health);
+                assert!(health.invulnerable > 0.);
+                return;
+            }
+            assert!(health.points > 0. && health.points < last);
+            last = health.points;
+        }
+        panic!("never converted");
+    }
+
+    #[test]
+    fn invulnerable_targets_are_passed_over() {
+        let mut app = health_app(&[("Rock", 0.), ("Scissors", 10.), ("Scissors", 25.)]);
+        let world = app.world_mut();
+        let mut query = world.query::<(&Id, &mut Health)>();
+        for (&id, mut health) in query.iter_mut(world) {
+            if id == Id(1) {
+                health.invulnerable = 10.;
+            }
+        }
+
+        app.update();
+        let max_health = SimConfig::default().max_health;
+        assert_eq!(find(&mut app, Id(1)).1.points, max_health);
+        assert!(find(&mut app, Id(2)).1.points < max_health);
+    }
+
+    #[test]
+    fn obstacles_cannot_push_through_walls() {
+        let edge = Arena::default().bounds().x;
+        // pushing out of it means pushing past the right wall
+        let obstacle = Obstacle::Circle {
+            center: (edge - 10., 0.),
+            radius: 40.,
+        };
+        let mut app = scenario_app(
+            SimConfig::default(),
+            &[("Rock", edge - 5.), ("Paper", -edge)],
+            vec![obstacle],
+        );
+
+        for _ in 0..10 {
+            let world = app.world_mut();
+            let mut query = world.query::<&Transform>();
+            assert!(query.iter(world).all(|t| t.translation.x.abs() <= edge));
+            app.update();
+        }
+    }
 }
@@ -0,0 +1,266 @@
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+    },
+};
+
+use crate::{
+    entities::{Faction, Health, Velocity},
+    events::GameOverEvent,
+    resources::{
+        config::SimConfig,
+        factions::Factions,
+        obstacle::{triangulate, Obstacle, Obstacles},
+        Arena, GameState, Seed,
+    },
+};
+
+use super::{
+    chart::ChartPlugin, debug::DebugPlugin, editor::EditorPlugin, hud::HudPlugin,
+    reload::HotReloadPlugin, sound::SoundPlugin,
+};
+
+/// Window, sprites and sounds on top of the simulation.
+pub struct GraphicsPlugin {
+    /// Asset path of a config file to hot reload.
+    pub watch: Option<String>,
+}
+
+impl Plugin for GraphicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Arena>();
+        let arena = *app.world().resource::<Arena>();
+        let title = match app.world().get_resource::<Seed>() {
+            Some(Seed(seed)) => format!("RPS - Simulation (seed {seed})"),
+            None => "RPS - Simulation".to_owned(),
+        };
+
+        app.add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: Some(Window {
+                        resolution: (arena.width, arena.height).into(),
+                        title,
+                        name: Some("RPS - Simulation".to_owned()),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+                .set(AssetPlugin {
+                    watch_for_changes_override: self.watch.is_some().then_some(true),
+                    ..Default::default()
+                }),
+        )
+        .add_plugins(DebugPlugin)
+        .add_plugins(HudPlugin)
+        .add_plugins(ChartPlugin)
+        .add_plugins(SoundPlugin)
+        .add_plugins(EditorPlugin)
+        .insert_resource(ClearColor(Color::Srgba(Srgba::rgb(240.0, 240.0, 240.0)))) // background
+        .add_systems(Startup, setup)
+        .add_systems(FixedFirst, restore_positions)
+        .add_systems(FixedLast, store_positions)
+        .add_systems(
+            RunFixedMainLoop,
+            interpolate_positions.in_set(RunFixedMainLoopSystem::AfterFixedMainLoop),
+        )
+        .add_systems(
+            Update,
+            (track_positions, update_sprites, flash_hits, show_result),
+        )
+        .add_systems(Update, draw_obstacles.run_if(resource_changed::<Obstacles>));
+
+        if let Some(path) = &self.watch {
+            app.add_plugins(HotReloadPlugin { path: path.clone() });
+        }
+    }
+}
+
+fn setup(mut commands: Commands) {
+    commands.spawn(Camera2d);
+}
+
+/// Position of an entity after each of the last two simulation ticks, so that
+/// frames falling between ticks are drawn in between.
+#[derive(Component)]
+pub(super) struct TickPositions {
+    pub(super) previous: Vec2,
+    pub(super) current: Vec2,
+}
+
+fn track_positions(mut commands: Commands, query: Query<(Entity, &Transform), Added<Velocity>>) {
+    for (entity, transform) in query.iter() {
+        let pos = transform.translation.xy();
+        commands.entity(entity).insert(TickPositions {
+            previous: pos,
+            current: pos,
+        });
+    }
+}
+
+// the simulation must see where entities really are, not where they are drawn
+fn restore_positions(mut query: Query<(&mut Transform, &TickPositions)>) {
+    for (mut transform, positions) in query.iter_mut() {
+        transform.translation.x = positions.current.x;
+        transform.translation.y = positions.current.y;
+    }
+}
+
+fn store_positions(mut query: Query<(&Transform, &mut TickPositions)>) {
+    for (transform, mut positions) in query.iter_mut() {
+        positions.previous = positions.current;
+        positions.current = transform.translation.xy();
+    }
+}
+
+fn interpolate_positions(
+    time: Res<Time<Fixed>>,
+    mut query: Query<(&mut Transform, &TickPositions)>,
+) {
+    let alpha = time.overstep_fraction();
+    for (mut transform, positions) in query.iter_mut() {
+        let pos = positions.previous.lerp(positions.current, alpha);
+        transform.translation.x = pos.x;
+        transform.translation.y = pos.y;
+    }
+}
+
+#[derive(Component)]
+struct ObstacleMesh;
+
+// obstacles change with every new match (config or scenario)
+fn draw_obstacles(
+    mut commands: Commands,
+    obstacles: Res<Obstacles>,
+    previous: Query<Entity, With<ObstacleMesh>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for entity in previous.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let material = materials.add(Color::srgb(0.35, 0.35, 0.4));
+    for obstacle in obstacles.0.iter() {
+        let (mesh, center) = match obstacle {
+            Obstacle::Rect { center, size } => {
+                (Mesh::from(Rectangle::new(size.0, size.1)), *center)
+            }
+            Obstacle::Circle { center, radius } => (Mesh::from(Circle::new(*radius)), *center),
+            Obstacle::Polygon { points } => (polygon_mesh(points), (0., 0.)),
+        };
+        commands.spawn((
+            ObstacleMesh,
+            Mesh2d(meshes.add(mesh)),
+            MeshMaterial2d(material.clone()),
+            Transform::from_xyz(center.0, center.1, 1.),
+        ));
+    }
+}
+
+fn polygon_mesh(points: &[(f32, f32)]) -> Mesh {
+    let corners: Vec<Vec2> = points.iter().copied().map(Vec2::from).collect();
+    let indices = triangulate(&corners)
+        .into_iter()
+        .flatten()
+        .map(|i| i as u32)
+        .collect();
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        corners.iter().map(|c| [c.x, c.y, 0.]).collect::<Vec<_>>(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 0., 1.]; corners.len()])
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0., 0.]; corners.len()])
+    .with_inserted_indices(Indices::U32(indices))
+}
+
+// entities get (or swap) their sprite whenever they join a faction
+fn update_sprites(
+    mut commands: Commands,
+    server: Res<AssetServer>,
+    config: Res<SimConfig>,
+    factions: Res<Factions>,
+    query: Query<(Entity, &Faction), Changed<Faction>>,
+) {
+    for (entity, &faction) in query.iter() {
+        let def = factions.def(faction);
+        let mut sprite = Sprite::from_image(server.load(&def.sprite));
+        sprite.custom_size = Some(Vec2::splat(config.sprite_size * 2.));
+        if let Some((r, g, b)) = def.tint {
+            sprite.color = Color::srgb(r, g, b);
+        }
+        commands.entity(entity).insert(sprite);
+    }
+}
+
+// entities turn red for a moment when hit
+fn flash_hits(
+    factions: Res<Factions>,
+    mut query: Query<(&Faction, &Health, &mut Sprite), Changed<Health>>,
+) {
+    for (&faction, health, mut sprite) in query.iter_mut() {
+        sprite.color = if health.flash > 0. {
+            Color::srgb(1., 0.3, 0.3)
+        } else {
+            match factions.def(faction).tint {
+                Some((r, g, b)) => Color::srgb(r, g, b),
+                None => Color::WHITE,
+            }
+        };
+    }
+}
+
+// result banner, gone as soon as the game leaves `GameOver`
+fn show_result(
+    mut commands: Commands,
+    factions: Res<Factions>,
+    mut game_over: EventReader<GameOverEvent>,
+) {
+    let Some(result) = game_over.read().last() else {
+        return;
+    };
+
+    let line = |text: String, font_size: f32| {
+        (
+            Text::new(text),
+            TextFont {
+                font_size,
+                ..Default::default()
+            },
+            TextColor(Color::WHITE),
+        )
+    };
+
+    commands
+        .spawn((
+            StateScoped(GameState::GameOver),
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(8.),
+                ..Default::default()
+            },
+            BackgroundColor(Color::srgba(0., 0., 0., 0.6)),
+        ))
+        .with_children(|c| {
+            c.spawn(line(format!("{} wins!", factions.name(result.winner)), 48.));
+            c.spawn(line(
+                format!(
+                    "{:.1}s simulated, {} conversions",
+                    result.elapsed.as_secs_f32(),
+                    result.conversions
+                ),
+                20.,
+            ));
+            c.spawn(line("Press R to restart".to_owned(), 16.));
+        });
+}
@@ -0,0 +1,211 @@
+use std::time::Duration;
+
+use bevy::{
+    app::ScheduleRunnerPlugin, ecs::schedule::ExecutorKind, prelude::*, state::app::StatesPlugin,
+    time::TimeUpdateStrategy,
+};
+
+use crate::{
+    events::GameOverEvent,
+    resources::{config::SimConfig, factions::Factions, GameState, MatchStats, Seed},
+};
+
+/// Runs the simulation as fast as possible without a window and, when
+/// `report`, prints the winner once a single faction remains.
+///
+/// Every frame advances time by exactly one simulation tick and systems run
+/// on a single thread in a fixed order, so a given [`Seed`] always plays the
+/// same match.
+pub struct HeadlessPlugin {
+    pub max_ticks: u32,
+    /// Off for the many matches of a tournament, which report on their own.
+    pub report: bool,
+}
+
+#[derive(Resource)]
+pub(super) struct HeadlessSettings {
+    max_ticks: u32,
+    report: bool,
+}
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SimConfig>();
+        let timestep = app.world().resource::<SimConfig>().timestep();
+
+        app.add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::ZERO)))
+            .add_plugins(StatesPlugin)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(timestep))
+            .insert_resource(HeadlessSettings {
+                max_ticks: self.max_ticks,
+                report: self.report,
+            })
+            .edit_schedule(Startup, single_threaded)
+            .edit_schedule(Update, single_threaded)
+            .edit_schedule(FixedUpdate, single_threaded)
+            .edit_schedule(PostUpdate, single_threaded)
+            .add_systems(Last, report_winner);
+    }
+}
+
+fn single_threaded(schedule: &mut Schedule) {
+    schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+}
+
+// waits for `GameOver` to be entered so its `OnEnter` systems get to run;
+// ticks are the simulated ones, like tournaments and replays count them
+pub(super) fn report_winner(
+    state: Res<State<GameState>>,
+    seed: Option<Res<Seed>>,
+    factions: Res<Factions>,
+    stats: Res<MatchStats>,
+    settings: Res<HeadlessSettings>,
+    mut game_over: EventReader<GameOverEvent>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let seed = seed.map_or_else(|| "-".to_owned(), |s| s.0.to_string());
+
+    if *state.get() == GameState::GameOver {
+        if let Some(result) = game_over.read().next() {
+            if settings.report {
+                println!(
+                    "seed {seed}: {} wins after {} ticks ({:.2}s simulated, {} conversions)",
+                    factions.name(result.winner),
+                    stats.ticks,
+                    result.elapsed.as_secs_f32(),
+                    result.conversions
+                );
+            }
+        }
+        exit.send(AppExit::Success);
+    } else if stats.ticks >= settings.max_ticks {
+        if settings.report {
+            println!("seed {seed}: no winner after {} ticks", stats.ticks);
+        }
+        exit.send(AppExit::Success);
+    }
+}
+
+/// A quiet headless match of `config` played from `seed`, for tests.
+#[cfg(test)]
+pub(crate) fn match_app(seed: u64, config: SimConfig) -> App {
+    use bevy_rand::{plugin::EntropyPlugin, prelude::WyRand};
+
+    use super::game::GameplayPlugin;
+
+    let mut app = App::new();
+    app.insert_resource(Seed(seed))
+        .insert_resource(config)
+        .add_plugins(HeadlessPlugin {
+            max_ticks: u32::MAX,
+            report: false,
+        })
+        .add_plugins(GameplayPlugin)
+        .add_plugins(EntropyPlugin::<WyRand>::with_seed(seed.to_le_bytes()));
+    app.finish();
+    app.cleanup();
+    app
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        entities::{Faction, Velocity},
+        events::ConversionEvent,
+        resources::layout::SpawnLayout,
+    };
+
+    fn final_transforms(seed: u64, ticks: usize) -> Vec<Transform> {
+        let mut app = match_app(seed, SimConfig::default());
+        for _ in 0..ticks {
+            app.update();
+        }
+
+        let world = app.world_mut();
+        let mut query = world.query_filtered::<&Transform, With<Velocity>>();
+        query.iter(world).copied().collect()
+    }
+
+    // plays a whole match, rendering a frame every `frame` of simulated time
+    fn play_match(seed: u64, frame: Duration) -> (Faction, Duration, u32) {
+        let config = SimConfig {
+            regions: 12,
+            ..Default::default()
+        };
+        let mut app = match_app(seed, config);
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(frame));
+
+        let mut cursor = app.world().resource::<Events<GameOverEvent>>().get_cursor();
+        for _ in 0..100_000 {
+            app.update();
+            let events = app.world().resource::<Events<GameOverEvent>>();
+            if let Some(result) = cursor.read(events).next() {
+                return (result.winner, result.elapsed, result.conversions);
+            }
+        }
+        panic!("seed {seed}: no winner at {frame:?} per frame");
+    }
+
+    #[test]
+    fn one_conversion_per_side_change() {
+        // packed tight so that several entities often touch the same one
+        let config = SimConfig {
+            layout: SpawnLayout::Mixed,
+            counts: vec![60, 60, 60],
+            arena_width: 300.,
+            arena_height: 300.,
+            region_radius: 60.,
+            ..Default::default()
+        };
+        let mut app = match_app(5, config);
+        let mut cursor = app
+            .world()
+            .resource::<Events<ConversionEvent>>()
+            .get_cursor();
+        let (mut events, mut changes) = (0, 0);
+
+        for _ in 0..600 {
+            let world = app.world_mut();
+            let before: HashMap<Entity, Faction> = world
+                .query::<(Entity, &Faction)>()
+                .iter(world)
+                .map(|(entity, &faction)| (entity, faction))
+                .collect();
+            app.update();
+
+            let world = app.world_mut();
+            changes += world
+                .query::<(Entity, &Faction)>()
+                .iter(world)
+                .filter(|(entity, faction)| before.get(entity).is_some_and(|f| f != *faction))
+                .count();
+            events += cursor
+                .read(world.resource::<Events<ConversionEvent>>())
+                .count();
+        }
+
+        assert!(changes > 0);
+        assert_eq!(events, changes);
+    }
+
+    #[test]
+    fn same_seed_same_match() {
+        let first = final_transforms(42, 600);
+        let second = final_transforms(42, 600);
+        assert!(!first.is_empty());
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn same_seed_same_winner_at_any_frame_rate() {
+        let tick = SimConfig::default().timestep();
+        let at_60 = play_match(7, tick);
+        let at_30 = play_match(7, tick * 2);
+        let at_144 = play_match(7, Duration::from_secs_f64(1. / 144.));
+        assert_eq!(at_60, at_30);
+        assert_eq!(at_60, at_144);
+    }
+}
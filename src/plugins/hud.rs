@@ -0,0 +1,142 @@
+use std::fmt::Write;
+
+use bevy::prelude::*;
+
+use crate::{
+    entities::Faction,
+    events::ToastEvent,
+    resources::{factions::Factions, DebugState, GameControl, GameState, MatchStats},
+};
+
+/// Live population counters in the top left corner, and toasts at the bottom.
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ToastEvent>()
+            .add_systems(Startup, setup_hud)
+            .add_systems(Update, (update_hud, show_toasts, expire_toasts));
+    }
+}
+
+#[derive(Component)]
+pub struct Hud;
+
+#[derive(Component)]
+pub struct Toast(Timer);
+
+const TOAST_SECS: f32 = 4.;
+
+fn setup_hud(mut commands: Commands) {
+    commands.spawn((
+        Hud,
+        Text::default(),
+        TextFont {
+            font_size: 14.,
+            ..Default::default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.),
+            left: Val::Px(8.),
+            padding: UiRect::all(Val::Px(6.)),
+            ..Default::default()
+        },
+        BackgroundColor(Color::srgba(0., 0., 0., 0.6)),
+    ));
+}
+
+fn update_hud(
+    debug: Res<DebugState>,
+    factions: Res<Factions>,
+    stats: Res<MatchStats>,
+    control: Res<GameControl>,
+    state: Res<State<GameState>>,
+    members: Query<&Faction>,
+    mut hud: Query<(&mut Text, &mut Visibility), With<Hud>>,
+) {
+    let Ok((mut text, mut vis)) = hud.get_single_mut() else {
+        return;
+    };
+
+    *vis = if debug.hud {
+        Visibility::Visible
+    } else {
+        Visibility::Hidden
+    };
+    if !debug.hud {
+        return;
+    }
+
+    let mut counts = vec![0; factions.len()];
+    for faction in members.iter() {
+        counts[faction.0] += 1;
+    }
+
+    text.0.clear();
+    for (faction, count) in factions.iter().zip(counts) {
+        let _ = writeln!(text.0, "{}: {count}", factions.name(faction));
+    }
+    let _ = writeln!(text.0, "conversions: {}", stats.conversions);
+    let _ = writeln!(text.0, "time: {:.1}s", stats.elapsed.as_secs_f32());
+    let _ = writeln!(text.0, "state: {:?}", state.get());
+    if control.skip {
+        let _ = writeln!(text.0, "speed: skipping to the end");
+    } else {
+        let _ = writeln!(text.0, "speed: {}x", control.speed);
+    }
+    if control.sound {
+        let _ = write!(text.0, "sound: {:.0}%", control.volume * 100.);
+        if control.panning {
+            let _ = write!(text.0, " (panned)");
+        }
+    } else {
+        let _ = write!(text.0, "sound: off");
+    }
+}
+
+fn show_toasts(
+    mut commands: Commands,
+    mut toasts: EventReader<ToastEvent>,
+    previous: Query<Entity, With<Toast>>,
+) {
+    let Some(toast) = toasts.read().last() else {
+        return;
+    };
+
+    // a new toast replaces the one on screen
+    for entity in previous.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    commands.spawn((
+        Toast(Timer::from_seconds(TOAST_SECS, TimerMode::Once)),
+        Text::new(toast.0.clone()),
+        TextFont {
+            font_size: 14.,
+            ..Default::default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(8.),
+            left: Val::Px(8.),
+            padding: UiRect::all(Val::Px(6.)),
+            ..Default::default()
+        },
+        BackgroundColor(Color::srgba(0.1, 0.1, 0.3, 0.8)),
+    ));
+}
+
+fn expire_toasts(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    mut query: Query<(Entity, &mut Toast)>,
+) {
+    for (entity, mut toast) in query.iter_mut() {
+        if toast.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -1,2 +1,10 @@
+pub mod chart;
 pub mod debug;
+pub mod editor;
 pub mod game;
+pub mod graphics;
+pub mod headless;
+pub mod hud;
+pub mod reload;
+pub mod replay;
+pub mod sound;
@@ -0,0 +1,179 @@
+use bevy::{asset::AssetLoadFailedEvent, prelude::*};
+
+use crate::{
+    entities::{FixedVision, Vision},
+    events::ToastEvent,
+    resources::{
+        config::{ConfigOverrides, SimConfig, SimConfigLoader},
+        factions::Factions,
+    },
+};
+
+/// Watches a config file under `assets/` and pushes every change into the
+/// running match, `--set` overrides still applying on top of it. Window size
+/// changes are ignored; region and spawn settings only show up on the next
+/// restart.
+pub struct HotReloadPlugin {
+    pub path: String,
+}
+
+#[derive(Resource)]
+struct WatchedConfig {
+    handle: Handle<SimConfig>,
+    overrides: ConfigOverrides,
+    /// Number of factions, which a reload cannot change.
+    factions: usize,
+}
+
+impl Plugin for HotReloadPlugin {
+    fn build(&self, app: &mut App) {
+        let path = self.path.clone();
+        app.init_asset::<SimConfig>()
+            .init_asset_loader::<SimConfigLoader>()
+            .add_systems(
+                Startup,
+                move |mut commands: Commands,
+                      server: Res<AssetServer>,
+                      factions: Res<Factions>,
+                      overrides: Option<Res<ConfigOverrides>>| {
+                    commands.insert_resource(WatchedConfig {
+                        handle: server.load(path.clone()),
+                        overrides: overrides.map(|o| o.clone()).unwrap_or_default(),
+                        factions: factions.len(),
+                    });
+                },
+            )
+            .add_systems(Update, (reload_config, report_reload_errors));
+    }
+}
+
+fn reload_config(
+    mut events: EventReader<AssetEvent<SimConfig>>,
+    watched: Res<WatchedConfig>,
+    assets: Res<Assets<SimConfig>>,
+    mut config: ResMut<SimConfig>,
+    mut fixed: ResMut<Time<Fixed>>,
+    mut visions: Query<&mut Vision, Without<FixedVision>>,
+    mut toasts: EventWriter<ToastEvent>,
+) {
+    for event in events.read() {
+        // the first load is what `--config` already read
+        if !event.is_modified(&watched.handle) {
+            continue;
+        }
+        let Some(new) = assets.get(&watched.handle) else {
+            continue;
+        };
+
+        let mut new = new.clone();
+        new.arena_width = config.arena_width;
+        new.arena_height = config.arena_height;
+
+        if let Err(e) = watched
+            .overrides
+            .apply(&mut new)
+            .and_then(|()| new.validate(watched.factions))
+        {
+            toasts.send(ToastEvent(format!("config not reloaded: {e}")));
+            continue;
+        }
+
+        let changes = config.diff(&new);
+        if changes.is_empty() {
+            continue;
+        }
+
+        for mut vision in visions.iter_mut() {
+            vision.0 = rescale_vision(vision.0, &config, &new);
+        }
+        if new.tick_rate != config.tick_rate {
+            fixed.set_timestep(new.timestep());
+        }
+        *config = new;
+        toasts.send(ToastEvent(format!(
+            "config reloaded\n{}",
+            changes.join("\n")
+        )));
+    }
+}
+
+// keeps every entity at the same relative spot of the vision range
+fn rescale_vision(vision: f32, old: &SimConfig, new: &SimConfig) -> f32 {
+    let t = (vision - old.sprite_size - old.vision_min) / (old.vision_max - old.vision_min);
+    new.sprite_size + new.vision_min + t * (new.vision_max - new.vision_min)
+}
+
+fn report_reload_errors(
+    mut failures: EventReader<AssetLoadFailedEvent<SimConfig>>,
+    mut toasts: EventWriter<ToastEvent>,
+) {
+    for failure in failures.read() {
+        toasts.send(ToastEvent(format!(
+            "config not reloaded: {}",
+            failure.error
+        )));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    // the config running after `new` was saved, with the toast it showed
+    fn reload(new: SimConfig) -> (SimConfig, Time<Fixed>, String) {
+        let mut world = World::new();
+        let mut assets = Assets::<SimConfig>::default();
+        let handle = assets.add(new);
+        world.insert_resource(assets);
+        world.insert_resource(WatchedConfig {
+            handle: handle.clone(),
+            overrides: ConfigOverrides::default(),
+            factions: 3,
+        });
+        world.insert_resource(SimConfig::default());
+        world.insert_resource(Time::<Fixed>::from_duration(
+            SimConfig::default().timestep(),
+        ));
+        world.init_resource::<Events<AssetEvent<SimConfig>>>();
+        world.init_resource::<Events<ToastEvent>>();
+        world.send_event(AssetEvent::Modified { id: handle.id() });
+
+        world.run_system_once(reload_config).unwrap();
+        let toast = world
+            .resource_mut::<Events<ToastEvent>>()
+            .drain()
+            .map(|toast| toast.0)
+            .collect();
+        (
+            world.resource::<SimConfig>().clone(),
+            *world.resource::<Time<Fixed>>(),
+            toast,
+        )
+    }
+
+    #[test]
+    fn reloads_apply_the_new_tick_rate() {
+        let (config, fixed, toast) = reload(SimConfig {
+            tick_rate: 30.,
+            ..Default::default()
+        });
+        assert_eq!(config.tick_rate, 30.);
+        assert_eq!(fixed.timestep(), config.timestep());
+        assert!(toast.starts_with("config reloaded"), "{toast}");
+    }
+
+    #[test]
+    fn out_of_range_tick_rates_are_not_reloaded() {
+        for tick_rate in [1e12, 1e-30] {
+            let (config, fixed, toast) = reload(SimConfig {
+                tick_rate,
+                ..Default::default()
+            });
+            assert_eq!(config, SimConfig::default());
+            assert_eq!(fixed.timestep(), config.timestep());
+            assert!(toast.starts_with("config not reloaded"), "{toast}");
+        }
+    }
+}
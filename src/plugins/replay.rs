@@ -0,0 +1,417 @@
+#![allow(clippy::type_complexity)]
+
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use bevy::{ecs::system::RunSystemOnce, prelude::*, window::PrimaryWindow};
+use bevy_rand::prelude::*;
+use rand::SeedableRng;
+
+use crate::{
+    entities::{Faction, Id},
+    events::{ConversionEvent, GameOverEvent},
+    resources::{
+        config::SimConfig,
+        factions::Factions,
+        replay::{ConversionRecord, Keyframe, Replay, KEYFRAME_TICKS},
+        scenario::Scenario,
+        Arena, GameControl, GameState, MatchStats, Seed,
+    },
+};
+
+use super::{
+    game::{cleanup_match, game_over_pending, run_tick},
+    headless::report_winner,
+};
+
+/// Records the first match into a [`Replay`] written to `path` once it ends,
+/// or when the app exits. Must be added after [`super::game::GameplayPlugin`].
+pub struct RecordPlugin {
+    pub path: PathBuf,
+}
+
+#[derive(Resource)]
+pub(super) struct Recording {
+    replay: Replay,
+    path: PathBuf,
+    saved: bool,
+}
+
+impl Plugin for RecordPlugin {
+    fn build(&self, app: &mut App) {
+        let world = app.world();
+        let seed = world.get_resource::<Seed>().map_or(0, |seed| seed.0);
+        let config = world.resource::<SimConfig>().clone();
+        let factions = world.resource::<Factions>().defs().to_vec();
+        let scenario = world.get_resource::<Scenario>().cloned();
+
+        app.insert_resource(Recording {
+            replay: Replay::new(seed, config, factions, scenario),
+            path: self.path.clone(),
+            saved: false,
+        })
+        .add_systems(FixedPostUpdate, record_tick)
+        .add_systems(Last, save_on_exit.after(report_winner));
+    }
+}
+
+/// Plays a recorded match again. The match is re-simulated from the replay's
+/// seed, config and factions (see `main`), and every tick is checked against
+/// the recorded conversions and keyframes.
+///
+/// When `interactive`, a timeline at the top of the window shows progress and
+/// can be clicked to jump anywhere; Left/Right seek by 5 seconds and Home goes
+/// back to the start. Pause and speed keys work as in a live match.
+pub struct ReplayPlugin {
+    pub replay: Replay,
+    pub interactive: bool,
+}
+
+#[derive(Resource, Default)]
+struct ReplayCheck {
+    last_tick: u32,
+    diverged: Option<u32>,
+    done: bool,
+}
+
+/// Tick the replay should jump to.
+#[derive(Resource, Default)]
+struct Seek(Option<u32>);
+
+#[derive(Component)]
+struct ReplayStatus;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.replay.clone())
+            .init_resource::<ReplayCheck>()
+            .add_systems(FixedPostUpdate, check_tick);
+
+        if self.interactive {
+            app.init_resource::<Seek>()
+                .add_systems(Startup, setup_status)
+                .add_systems(Update, (control_seek, draw_timeline, update_status))
+                .add_systems(PostUpdate, seek);
+        }
+    }
+}
+
+/// Conversions sent during the last tick, with entities as [`Id`]s.
+fn tick_conversions(
+    tick: u32,
+    conversions: &mut EventReader<ConversionEvent>,
+    ids: &Query<&Id>,
+) -> Vec<ConversionRecord> {
+    conversions
+        .read()
+        .filter_map(|conversion| {
+            let actor = ids.get(conversion.actor).ok()?;
+            let target = ids.get(conversion.target).ok()?;
+            Some(ConversionRecord {
+                tick,
+                actor: actor.0,
+                target: target.0,
+                faction: conversion.faction.0,
+            })
+        })
+        .collect()
+}
+
+fn keyframe(tick: u32, entities: &Query<(&Id, &Faction, &Transform)>) -> Keyframe {
+    Keyframe {
+        tick,
+        entities: entities
+            .iter()
+            .sort::<&Id>()
+            .map(|(_, faction, transform)| {
+                (faction.0, transform.translation.x, transform.translation.y)
+            })
+            .collect(),
+    }
+}
+
+fn record_tick(
+    stats: Res<MatchStats>,
+    mut recording: ResMut<Recording>,
+    mut conversions: EventReader<ConversionEvent>,
+    mut game_over: EventReader<GameOverEvent>,
+    ids: Query<&Id>,
+    entities: Query<(&Id, &Faction, &Transform)>,
+) {
+    // a paused tick changes nothing, and only the first match is recorded
+    if stats.ticks == recording.replay.ticks || recording.saved {
+        conversions.clear();
+        game_over.clear();
+        return;
+    }
+
+    let tick = stats.ticks;
+    let replay = &mut recording.replay;
+    replay.ticks = tick;
+    replay
+        .conversions
+        .extend(tick_conversions(tick, &mut conversions, &ids));
+    if tick.is_multiple_of(KEYFRAME_TICKS) {
+        replay.keyframes.push(keyframe(tick, &entities));
+    }
+
+    if let Some(result) = game_over.read().last() {
+        replay.winner = Some(result.winner.0);
+        save(&mut recording);
+    }
+}
+
+fn save_on_exit(mut exit: EventReader<AppExit>, mut recording: ResMut<Recording>) {
+    if exit.read().next().is_some() && !recording.saved {
+        save(&mut recording);
+    }
+}
+
+fn save(recording: &mut Recording) {
+    match recording.replay.save(&recording.path) {
+        Ok(()) => println!("replay written to {}", recording.path.display()),
+        Err(e) => eprintln!("cannot write {}: {e}", recording.path.display()),
+    }
+    recording.saved = true;
+}
+
+fn check_tick(
+    replay: Res<Replay>,
+    stats: Res<MatchStats>,
+    mut check: ResMut<ReplayCheck>,
+    mut conversions: EventReader<ConversionEvent>,
+    mut game_over: EventReader<GameOverEvent>,
+    ids: Query<&Id>,
+    entities: Query<(&Id, &Faction, &Transform)>,
+) {
+    if stats.ticks == check.last_tick {
+        conversions.clear();
+        game_over.clear();
+        return;
+    }
+    check.last_tick = stats.ticks;
+
+    let tick = stats.ticks;
+    let mut matches = tick_conversions(tick, &mut conversions, &ids) == replay.conversions_at(tick);
+    if let Some(recorded) = replay.keyframe_at(tick) {
+        matches &= keyframe(tick, &entities) == *recorded;
+    }
+    let result = game_over.read().last();
+    if let Some(result) = result {
+        matches &= replay.winner == Some(result.winner.0) && replay.ticks == tick;
+    }
+
+    if !matches && check.diverged.is_none() {
+        check.diverged = Some(tick);
+        eprintln!("replay diverged from the recording at tick {tick}");
+    }
+
+    if result.is_some() && !check.done {
+        check.done = true;
+        if check.diverged.is_none() {
+            println!("replay matches the recording ({tick} ticks)");
+        }
+    }
+}
+
+// seconds skipped by Left or Right
+const SEEK_SECS: f32 = 5.;
+
+// timeline geometry, in pixels from the top of the window
+const TIMELINE_Y: f32 = 12.;
+const TIMELINE_MARGIN: f32 = 12.;
+
+fn control_seek(
+    replay: Res<Replay>,
+    stats: Res<MatchStats>,
+    arena: Res<Arena>,
+    keys: Res<ButtonInput<KeyCode>>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    mut seek: ResMut<Seek>,
+) {
+    let step = (SEEK_SECS * replay.config.tick_rate) as u32;
+    let current = seek.0.unwrap_or(stats.ticks);
+
+    if keys.just_pressed(KeyCode::ArrowLeft) {
+        seek.0 = Some(current.saturating_sub(step));
+    }
+    if keys.just_pressed(KeyCode::ArrowRight) {
+        seek.0 = Some((current + step).min(replay.ticks));
+    }
+    if keys.just_pressed(KeyCode::Home) {
+        seek.0 = Some(0);
+    }
+
+    if buttons.just_pressed(MouseButton::Left) {
+        let Some(cursor) = window.get_single().ok().and_then(|w| w.cursor_position()) else {
+            return;
+        };
+        let width = arena.width - 2. * TIMELINE_MARGIN;
+        if (cursor.y - TIMELINE_Y).abs() <= 8. {
+            let fraction = ((cursor.x - TIMELINE_MARGIN) / width).clamp(0., 1.);
+            seek.0 = Some((fraction * replay.ticks as f32) as u32);
+        }
+    }
+}
+
+// how long a frame may spend re-simulating before drawing again
+const SEEK_BUDGET: Duration = Duration::from_millis(100);
+
+// going back restarts the match from its seed, going forward runs ticks back
+// to back (even while paused) until the target is reached
+fn seek(world: &mut World) {
+    let Some(target) = world.resource::<Seek>().0 else {
+        return;
+    };
+
+    let state = *world.resource::<State<GameState>>().get();
+    let ticks = world.resource::<MatchStats>().ticks;
+    if state == GameState::LoadingRes {
+        return;
+    }
+
+    if target < ticks {
+        if state != GameState::GameOver {
+            let _ = world.run_system_once(cleanup_match);
+        }
+        let seed = world.resource::<Replay>().seed;
+        world.insert_resource(GlobalEntropy::<WyRand>::from_seed(seed.to_le_bytes()));
+        // the match is checked again from the start
+        world.insert_resource(ReplayCheck::default());
+        world
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::LoadingRes);
+        return;
+    }
+
+    let start = Instant::now();
+    while world.resource::<MatchStats>().ticks < target && start.elapsed() < SEEK_BUDGET {
+        if game_over_pending(world.resource::<NextState<GameState>>())
+            || state == GameState::GameOver
+        {
+            break;
+        }
+        world.resource_mut::<GameControl>().step = true;
+        run_tick(world);
+    }
+
+    let ticks = world.resource::<MatchStats>().ticks;
+    if ticks >= target || state == GameState::GameOver {
+        world.resource_mut::<Seek>().0 = None;
+    }
+}
+
+fn draw_timeline(
+    replay: Res<Replay>,
+    stats: Res<MatchStats>,
+    check: Res<ReplayCheck>,
+    arena: Res<Arena>,
+    mut gizmos: Gizmos,
+) {
+    let left = -arena.width / 2. + TIMELINE_MARGIN;
+    let right = arena.width / 2. - TIMELINE_MARGIN;
+    let y = arena.height / 2. - TIMELINE_Y;
+    let at = |tick: u32| left + (right - left) * tick as f32 / replay.ticks.max(1) as f32;
+
+    gizmos.line_2d(
+        Vec2::new(left, y),
+        Vec2::new(right, y),
+        Color::srgb(0.4, 0.4, 0.4),
+    );
+    gizmos.line_2d(
+        Vec2::new(left, y),
+        Vec2::new(at(stats.ticks), y),
+        Color::srgb(0.2, 0.6, 1.),
+    );
+    gizmos.line_2d(
+        Vec2::new(at(stats.ticks), y - 6.),
+        Vec2::new(at(stats.ticks), y + 6.),
+        Color::WHITE,
+    );
+    if let Some(tick) = check.diverged {
+        gizmos.line_2d(
+            Vec2::new(at(tick), y - 6.),
+            Vec2::new(at(tick), y + 6.),
+            Color::srgb(1., 0.2, 0.2),
+        );
+    }
+}
+
+fn setup_status(mut commands: Commands) {
+    commands.spawn((
+        ReplayStatus,
+        Text::default(),
+        TextFont {
+            font_size: 14.,
+            ..Default::default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(TIMELINE_Y + 10.),
+            right: Val::Px(TIMELINE_MARGIN),
+            padding: UiRect::all(Val::Px(6.)),
+            ..Default::default()
+        },
+        BackgroundColor(Color::srgba(0., 0., 0., 0.5)),
+    ));
+}
+
+fn update_status(
+    replay: Res<Replay>,
+    stats: Res<MatchStats>,
+    check: Res<ReplayCheck>,
+    mut status: Query<&mut Text, With<ReplayStatus>>,
+) {
+    let Ok(mut text) = status.get_single_mut() else {
+        return;
+    };
+
+    let verdict = match check.diverged {
+        Some(tick) => format!("diverged at tick {tick}"),
+        None => "matches the recording".to_owned(),
+    };
+    text.0 = format!("replay: tick {} / {}\n{verdict}", stats.ticks, replay.ticks);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::headless::match_app;
+
+    #[test]
+    fn seeks_forward_while_paused() {
+        let mut app = match_app(3, SimConfig::default());
+        app.init_resource::<Seek>();
+
+        for _ in 0..10 {
+            app.update();
+        }
+        app.world_mut()
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Paused);
+        app.update();
+        let world = app.world_mut();
+        assert_eq!(
+            *world.resource::<State<GameState>>().get(),
+            GameState::Paused
+        );
+
+        // the pause key keeps a state pending every frame
+        let target = world.resource::<MatchStats>().ticks + 30;
+        world.resource_mut::<Seek>().0 = Some(target);
+        for _ in 0..100 {
+            if world.resource::<Seek>().0.is_none() {
+                break;
+            }
+            world
+                .resource_mut::<NextState<GameState>>()
+                .set(GameState::Paused);
+            seek(world);
+        }
+        assert_eq!(world.resource::<MatchStats>().ticks, target);
+    }
+}
@@ -0,0 +1,106 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_kira_audio::prelude::{AudioSource, *};
+
+use crate::{
+    events::ConversionEvent,
+    resources::{factions::Factions, Arena, GameControl},
+};
+
+/// Conversion sounds, spread over a few channels so a mass conversion cannot
+/// pile up more than [`SOUND_SLOTS`] clips at once.
+pub struct SoundPlugin;
+
+pub const SOUND_SLOTS: usize = 4;
+
+/// One kira channel per clip that may play at the same time.
+#[derive(Resource)]
+pub struct SoundSlot<const I: usize>;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(AudioPlugin)
+            .add_audio_channel::<SoundSlot<0>>()
+            .add_audio_channel::<SoundSlot<1>>()
+            .add_audio_channel::<SoundSlot<2>>()
+            .add_audio_channel::<SoundSlot<3>>()
+            .add_systems(Update, (mute_sounds, play_conversion_sounds).chain());
+    }
+}
+
+#[derive(SystemParam)]
+struct SoundSlots<'w> {
+    slot0: Res<'w, AudioChannel<SoundSlot<0>>>,
+    slot1: Res<'w, AudioChannel<SoundSlot<1>>>,
+    slot2: Res<'w, AudioChannel<SoundSlot<2>>>,
+    slot3: Res<'w, AudioChannel<SoundSlot<3>>>,
+}
+
+impl SoundSlots<'_> {
+    fn is_playing(&self, slot: usize) -> bool {
+        match slot {
+            0 => self.slot0.is_playing_sound(),
+            1 => self.slot1.is_playing_sound(),
+            2 => self.slot2.is_playing_sound(),
+            _ => self.slot3.is_playing_sound(),
+        }
+    }
+
+    fn play(&self, slot: usize, source: Handle<AudioSource>) -> PlayAudioCommand<'_> {
+        match slot {
+            0 => self.slot0.play(source),
+            1 => self.slot1.play(source),
+            2 => self.slot2.play(source),
+            _ => self.slot3.play(source),
+        }
+    }
+
+    fn stop(&self) {
+        self.slot0.stop();
+        self.slot1.stop();
+        self.slot2.stop();
+        self.slot3.stop();
+    }
+}
+
+// cut whatever is still playing as soon as sound is switched off
+fn mute_sounds(control: Res<GameControl>, slots: SoundSlots) {
+    if control.is_changed() && !control.sound {
+        slots.stop();
+    }
+}
+
+fn play_conversion_sounds(
+    server: Res<AssetServer>,
+    control: Res<GameControl>,
+    factions: Res<Factions>,
+    arena: Res<Arena>,
+    slots: SoundSlots,
+    transforms: Query<&Transform>,
+    mut conversions: EventReader<ConversionEvent>,
+) {
+    if !control.sound {
+        conversions.clear();
+        return;
+    }
+
+    // a channel only reports its clip once kira picked it up, so slots used
+    // this frame are tracked here
+    let mut free = (0..SOUND_SLOTS).filter(|&slot| !slots.is_playing(slot));
+    for conversion in conversions.read() {
+        let Some(slot) = free.next() else {
+            break;
+        };
+
+        let def = factions.def(conversion.faction);
+        let mut command = slots.play(slot, server.load(&def.sound));
+        command.with_volume(control.volume * def.volume);
+        if control.panning {
+            if let Ok(transform) = transforms.get(conversion.target) {
+                let x = transform.translation.x / arena.width + 0.5;
+                command.with_panning(x.clamp(0., 1.) as f64);
+            }
+        }
+    }
+    // whatever did not find a free channel is dropped
+    conversions.clear();
+}
@@ -0,0 +1,599 @@
+use std::{fmt, fs, io, path::Path, str::FromStr, time::Duration};
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::{Asset, Resource, TypePath},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::constants::*;
+
+use super::{layout::SpawnLayout, obstacle::Obstacle, Arena};
+
+/// Every tunable of the simulation. Fields missing from a config file keep
+/// their default from [`crate::constants`].
+///
+/// It is also an asset so that a watched config file can be reloaded while
+/// the match runs.
+#[derive(Resource, Asset, TypePath, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimConfig {
+    /// Simulation ticks per second, independent of the render frame rate,
+    /// within [`SimConfig::TICK_RATES`].
+    pub tick_rate: f32,
+    /// Top speed of every entity, in pixels per second.
+    pub max_speed: f32,
+    /// How fast an entity can change its velocity, in pixels per second².
+    pub max_accel: f32,
+    /// Half the side of a sprite; two sprites closer than twice this touch.
+    pub sprite_size: f32,
+    /// How the entities are laid out at the start, see [`SpawnLayout`].
+    pub layout: SpawnLayout,
+    /// Entities every faction starts with, in declaration order. Factions
+    /// left out get `entities_per_region` for each region they would get
+    /// with the `Regions` layout.
+    pub counts: Vec<usize>,
+    pub regions: usize,
+    pub entities_per_region: usize,
+    pub region_radius: f32,
+    /// Vision radius (on top of `sprite_size`) is drawn from this range.
+    pub vision_min: f32,
+    pub vision_max: f32,
+    /// Angle of the cone ahead of an entity it sees in, in degrees; 360 sees
+    /// all around.
+    pub fov: f32,
+    /// Whether entities hide what is behind them, like obstacles do.
+    pub occlusion: bool,
+    /// How long an entity keeps heading for (or away from) where it last
+    /// saw someone, in seconds.
+    pub memory_secs: f32,
+    /// Steering weights, as fractions of `max_speed`: chasing the closest
+    /// target in sight, running from the closest enemy in sight, keeping
+    /// apart from allies and drifting around.
+    pub seek_weight: f32,
+    pub flee_weight: f32,
+    pub separation_weight: f32,
+    pub wander_weight: f32,
+    /// Replaces `wander_weight` while no target is in sight, so that
+    /// entities roam the arena looking for one.
+    pub explore_weight: f32,
+    /// How fast the wandering heading may turn, in degrees per second.
+    pub wander_turn: f32,
+    /// How long an entity rests after converting someone, in seconds.
+    pub rest_secs: f32,
+    /// Top speed in every behavior, as fractions of `max_speed`.
+    pub wander_speed: f32,
+    pub chase_speed: f32,
+    pub flee_speed: f32,
+    pub rest_speed: f32,
+    /// Energy lost per second of chasing or fleeing, out of 1.
+    pub energy_drain: f32,
+    /// Energy won back per second of wandering or resting, out of 1.
+    pub energy_regain: f32,
+    /// Fraction of its top speed an entity keeps with no energy left.
+    pub exhausted_speed: f32,
+    /// Whether touching a target converts it at once or wears its health
+    /// down, see [`ConversionMode`].
+    pub conversion: ConversionMode,
+    /// Hit points of every entity with [`ConversionMode::Health`].
+    pub max_health: f32,
+    /// Hit points a touching enemy takes away per second.
+    pub contact_damage: f32,
+    /// Seconds a freshly converted entity takes no damage.
+    pub invulnerable_secs: f32,
+    pub arena_width: f32,
+    pub arena_height: f32,
+    /// Walls and rocks entities go around and cannot see through.
+    pub obstacles: Vec<Obstacle>,
+}
+
+/// The `--set` overrides of the command line, applied again on top of every
+/// reload of a watched config file.
+#[derive(Resource, Default, Debug, Clone)]
+pub struct ConfigOverrides(pub Vec<(String, String)>);
+
+impl ConfigOverrides {
+    pub fn apply(&self, config: &mut SimConfig) -> Result<(), ConfigError> {
+        for (key, value) in self.0.iter() {
+            config.set(key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// How a target changes sides.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConversionMode {
+    /// As soon as it is touched.
+    #[default]
+    Instant,
+    /// Once touching wore its health down to 0.
+    Health,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    UnknownKey(String),
+    BadValue { key: String, value: String },
+    Invalid { key: &'static str, reason: String },
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            tick_rate: TICK_RATE,
+            max_speed: MAX_SPEED,
+            max_accel: MAX_ACCEL,
+            sprite_size: SPRITE_SIZE,
+            layout: SpawnLayout::default(),
+            counts: Vec::new(),
+            regions: REGIONS,
+            entities_per_region: ENTITIES_PER_REGION,
+            region_radius: REGION_RADIUS,
+            vision_min: VISION_MIN,
+            vision_max: VISION_MAX,
+            fov: FOV,
+            occlusion: OCCLUSION,
+            memory_secs: MEMORY_SECS,
+            seek_weight: SEEK_WEIGHT,
+            flee_weight: FLEE_WEIGHT,
+            separation_weight: SEPARATION_WEIGHT,
+            wander_weight: WANDER_WEIGHT,
+            explore_weight: EXPLORE_WEIGHT,
+            wander_turn: WANDER_TURN,
+            rest_secs: REST_SECS,
+            wander_speed: WANDER_SPEED,
+            chase_speed: CHASE_SPEED,
+            flee_speed: FLEE_SPEED,
+            rest_speed: REST_SPEED,
+            energy_drain: ENERGY_DRAIN,
+            energy_regain: ENERGY_REGAIN,
+            exhausted_speed: EXHAUSTED_SPEED,
+            conversion: ConversionMode::default(),
+            max_health: MAX_HEALTH,
+            contact_damage: CONTACT_DAMAGE,
+            invulnerable_secs: INVULNERABLE_SECS,
+            arena_width: ARENA_WIDTH,
+            arena_height: ARENA_HEIGHT,
+            obstacles: Vec::new(),
+        }
+    }
+}
+
+impl SimConfig {
+    /// Tick rates a timestep can be made of; far outside of it the step
+    /// rounds to zero or does not fit a `Duration`.
+    pub const TICK_RATES: std::ops::RangeInclusive<f32> = 1.0..=1000.0;
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        Self::from_ron(&text)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, ConfigError> {
+        ron::from_str(text).map_err(ConfigError::Parse)
+    }
+
+    /// Overrides a single field by name, e.g. `("max_speed", "80")`.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, ConfigError> {
+            value.trim().parse().map_err(|_| ConfigError::BadValue {
+                key: key.to_owned(),
+                value: value.to_owned(),
+            })
+        }
+
+        match key {
+            "tick_rate" => self.tick_rate = parse(key, value)?,
+            "max_speed" => self.max_speed = parse(key, value)?,
+            "max_accel" => self.max_accel = parse(key, value)?,
+            "sprite_size" => self.sprite_size = parse(key, value)?,
+            "layout" => self.layout = parse(key, value)?,
+            "counts" => {
+                self.counts = value
+                    .split(',')
+                    .filter(|count| !count.trim().is_empty())
+                    .map(|count| parse(key, count))
+                    .collect::<Result<_, _>>()?
+            }
+            "regions" => self.regions = parse(key, value)?,
+            "entities_per_region" => self.entities_per_region = parse(key, value)?,
+            "region_radius" => self.region_radius = parse(key, value)?,
+            "vision_min" => self.vision_min = parse(key, value)?,
+            "vision_max" => self.vision_max = parse(key, value)?,
+            "fov" => self.fov = parse(key, value)?,
+            "occlusion" => self.occlusion = parse(key, value)?,
+            "memory_secs" => self.memory_secs = parse(key, value)?,
+            "seek_weight" => self.seek_weight = parse(key, value)?,
+            "flee_weight" => self.flee_weight = parse(key, value)?,
+            "separation_weight" => self.separation_weight = parse(key, value)?,
+            "wander_weight" => self.wander_weight = parse(key, value)?,
+            "explore_weight" => self.explore_weight = parse(key, value)?,
+            "wander_turn" => self.wander_turn = parse(key, value)?,
+            "rest_secs" => self.rest_secs = parse(key, value)?,
+            "wander_speed" => self.wander_speed = parse(key, value)?,
+            "chase_speed" => self.chase_speed = parse(key, value)?,
+            "flee_speed" => self.flee_speed = parse(key, value)?,
+            "rest_speed" => self.rest_speed = parse(key, value)?,
+            "energy_drain" => self.energy_drain = parse(key, value)?,
+            "energy_regain" => self.energy_regain = parse(key, value)?,
+            "exhausted_speed" => self.exhausted_speed = parse(key, value)?,
+            "conversion" => self.conversion = parse(key, value)?,
+            "max_health" => self.max_health = parse(key, value)?,
+            "contact_damage" => self.contact_damage = parse(key, value)?,
+            "invulnerable_secs" => self.invulnerable_secs = parse(key, value)?,
+            "arena_width" => self.arena_width = parse(key, value)?,
+            "arena_height" => self.arena_height = parse(key, value)?,
+            "obstacles" => {
+                self.obstacles = ron::from_str(value).map_err(|_| ConfigError::BadValue {
+                    key: key.to_owned(),
+                    value: value.to_owned(),
+                })?
+            }
+            _ => return Err(ConfigError::UnknownKey(key.to_owned())),
+        }
+
+        Ok(())
+    }
+
+    /// Name and value of every field, in declaration order.
+    pub fn fields(&self) -> [(&'static str, String); 35] {
+        [
+            ("tick_rate", self.tick_rate.to_string()),
+            ("max_speed", self.max_speed.to_string()),
+            ("max_accel", self.max_accel.to_string()),
+            ("sprite_size", self.sprite_size.to_string()),
+            ("layout", self.layout.to_string()),
+            (
+                "counts",
+                self.counts
+                    .iter()
+                    .map(|count| count.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+            ("regions", self.regions.to_string()),
+            ("entities_per_region", self.entities_per_region.to_string()),
+            ("region_radius", self.region_radius.to_string()),
+            ("vision_min", self.vision_min.to_string()),
+            ("vision_max", self.vision_max.to_string()),
+            ("fov", self.fov.to_string()),
+            ("occlusion", self.occlusion.to_string()),
+            ("memory_secs", self.memory_secs.to_string()),
+            ("seek_weight", self.seek_weight.to_string()),
+            ("flee_weight", self.flee_weight.to_string()),
+            ("separation_weight", self.separation_weight.to_string()),
+            ("wander_weight", self.wander_weight.to_string()),
+            ("explore_weight", self.explore_weight.to_string()),
+            ("wander_turn", self.wander_turn.to_string()),
+            ("rest_secs", self.rest_secs.to_string()),
+            ("wander_speed", self.wander_speed.to_string()),
+            ("chase_speed", self.chase_speed.to_string()),
+            ("flee_speed", self.flee_speed.to_string()),
+            ("rest_speed", self.rest_speed.to_string()),
+            ("energy_drain", self.energy_drain.to_string()),
+            ("energy_regain", self.energy_regain.to_string()),
+            ("exhausted_speed", self.exhausted_speed.to_string()),
+            ("conversion", self.conversion.to_string()),
+            ("max_health", self.max_health.to_string()),
+            ("contact_damage", self.contact_damage.to_string()),
+            ("invulnerable_secs", self.invulnerable_secs.to_string()),
+            ("arena_width", self.arena_width.to_string()),
+            ("arena_height", self.arena_height.to_string()),
+            (
+                "obstacles",
+                ron::to_string(&self.obstacles).unwrap_or_default(),
+            ),
+        ]
+    }
+
+    /// Entities every faction starts with, see [`SimConfig::counts`].
+    pub fn faction_counts(&self, factions: usize) -> Vec<usize> {
+        (0..factions)
+            .map(|f| {
+                self.counts.get(f).copied().unwrap_or_else(|| {
+                    self.regions.saturating_sub(f).div_ceil(factions) * self.entities_per_region
+                })
+            })
+            .collect()
+    }
+
+    /// Simulated time covered by a single tick.
+    pub fn timestep(&self) -> Duration {
+        Duration::from_secs_f64(1. / self.tick_rate as f64)
+    }
+
+    /// Fields whose value differs in `other`, as `key: old -> new`.
+    pub fn diff(&self, other: &Self) -> Vec<String> {
+        self.fields()
+            .into_iter()
+            .zip(other.fields())
+            .filter(|((_, old), (_, new))| old != new)
+            .map(|((key, old), (_, new))| format!("{key}: {old} -> {new}"))
+            .collect()
+    }
+
+    /// Checks every field, `counts` against the number of `factions`.
+    pub fn validate(&self, factions: usize) -> Result<(), ConfigError> {
+        fn check(ok: bool, key: &'static str, reason: String) -> Result<(), ConfigError> {
+            if ok {
+                Ok(())
+            } else {
+                Err(ConfigError::Invalid { key, reason })
+            }
+        }
+
+        // infinite sizes and speeds turn positions into NaN or panic at spawn
+        let positive = |key: &'static str, value: f32| {
+            check(
+                value.is_finite() && value > 0.,
+                key,
+                format!("must be positive and finite, got {value}"),
+            )
+        };
+        let non_negative = |key: &'static str, value: f32| {
+            check(
+                value.is_finite() && value >= 0.,
+                key,
+                format!("cannot be negative or infinite, got {value}"),
+            )
+        };
+
+        check(
+            Self::TICK_RATES.contains(&self.tick_rate),
+            "tick_rate",
+            format!(
+                "must be within [{}, {}] ticks per second, got {}",
+                Self::TICK_RATES.start(),
+                Self::TICK_RATES.end(),
+                self.tick_rate
+            ),
+        )?;
+        positive("max_speed", self.max_speed)?;
+        positive("max_accel", self.max_accel)?;
+        positive("sprite_size", self.sprite_size)?;
+        positive("region_radius", self.region_radius)?;
+        positive("max_health", self.max_health)?;
+        positive("contact_damage", self.contact_damage)?;
+        for (key, side) in [
+            ("arena_width", self.arena_width),
+            ("arena_height", self.arena_height),
+        ] {
+            check(
+                side.is_finite() && side > Arena::MIN_SIDE,
+                key,
+                format!(
+                    "must be greater than {} and finite, got {side}",
+                    Arena::MIN_SIDE
+                ),
+            )?;
+        }
+        check(
+            self.regions > 0,
+            "regions",
+            "at least one region is needed".to_owned(),
+        )?;
+        check(
+            self.entities_per_region > 0,
+            "entities_per_region",
+            "at least one entity per region is needed".to_owned(),
+        )?;
+        check(
+            self.counts.len() <= factions,
+            "counts",
+            format!(
+                "lists {} factions but there are only {factions}",
+                self.counts.len()
+            ),
+        )?;
+        check(
+            self.faction_counts(factions).iter().any(|&count| count > 0),
+            "counts",
+            "at least one faction must start with an entity".to_owned(),
+        )?;
+        non_negative("vision_min", self.vision_min)?;
+        check(
+            self.vision_max.is_finite() && self.vision_max > self.vision_min,
+            "vision_max",
+            format!(
+                "must be finite and greater than vision_min ({}), got {}",
+                self.vision_min, self.vision_max
+            ),
+        )?;
+        check(
+            self.fov > 0. && self.fov <= 360.,
+            "fov",
+            format!("must be within (0, 360] degrees, got {}", self.fov),
+        )?;
+        for (key, value) in [
+            ("memory_secs", self.memory_secs),
+            ("seek_weight", self.seek_weight),
+            ("flee_weight", self.flee_weight),
+            ("separation_weight", self.separation_weight),
+            ("wander_weight", self.wander_weight),
+            ("explore_weight", self.explore_weight),
+            ("wander_turn", self.wander_turn),
+            ("energy_drain", self.energy_drain),
+            ("energy_regain", self.energy_regain),
+            ("invulnerable_secs", self.invulnerable_secs),
+            ("rest_secs", self.rest_secs),
+        ] {
+            non_negative(key, value)?;
+        }
+        for (key, speed) in [
+            ("wander_speed", self.wander_speed),
+            ("chase_speed", self.chase_speed),
+            ("flee_speed", self.flee_speed),
+            ("rest_speed", self.rest_speed),
+            ("exhausted_speed", self.exhausted_speed),
+        ] {
+            check(
+                (0. ..=1.).contains(&speed),
+                key,
+                format!("must be within [0, 1], got {speed}"),
+            )?;
+        }
+        for obstacle in self.obstacles.iter() {
+            check(
+                obstacle.has_area(),
+                "obstacles",
+                format!("{obstacle:?} has no area"),
+            )?;
+        }
+        check(
+            self.region_radius * 2. < self.arena_width.min(self.arena_height),
+            "region_radius",
+            format!(
+                "a region of radius {} does not fit in a {}x{} arena",
+                self.region_radius, self.arena_width, self.arena_height
+            ),
+        )?;
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct SimConfigLoader;
+
+impl AssetLoader for SimConfigLoader {
+    type Asset = SimConfig;
+    type Settings = ();
+    type Error = ConfigError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<SimConfig, ConfigError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(ConfigError::Io)?;
+        ron::de::from_bytes(&bytes).map_err(ConfigError::Parse)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ron"]
+    }
+}
+
+impl fmt::Display for ConversionMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Instant => "instant",
+            Self::Health => "health",
+        })
+    }
+}
+
+impl FromStr for ConversionMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        [Self::Instant, Self::Health]
+            .into_iter()
+            .find(|mode| mode.to_string().eq_ignore_ascii_case(s.trim()))
+            .ok_or(())
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "cannot read config file: {e}"),
+            Self::Parse(e) => write!(f, "invalid config file: {e}"),
+            Self::UnknownKey(key) => write!(f, "unknown config key `{key}`"),
+            Self::BadValue { key, value } => write!(f, "`{value}` is not a valid `{key}`"),
+            Self::Invalid { key, reason } => write!(f, "`{key}` {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_overrides_fields_by_name() {
+        let mut config = SimConfig::default();
+        config.set("max_speed", "80").unwrap();
+        config.set("layout", "rings").unwrap();
+        config.set("counts", "40, 30,20").unwrap();
+        config.set("conversion", "Health").unwrap();
+        config
+            .set("obstacles", "[Circle(center: (0.0, 0.0), radius: 10.0)]")
+            .unwrap();
+
+        assert_eq!(config.max_speed, 80.);
+        assert_eq!(config.layout, SpawnLayout::Rings);
+        assert_eq!(config.counts, vec![40, 30, 20]);
+        assert_eq!(config.conversion, ConversionMode::Health);
+        assert_eq!(config.obstacles.len(), 1);
+    }
+
+    #[test]
+    fn set_rejects_unknown_keys_and_bad_values() {
+        let mut config = SimConfig::default();
+        assert!(matches!(
+            config.set("max_sped", "80"),
+            Err(ConfigError::UnknownKey(key)) if key == "max_sped"
+        ));
+        assert!(matches!(
+            config.set("max_speed", "fast"),
+            Err(ConfigError::BadValue { key, .. }) if key == "max_speed"
+        ));
+        assert!(matches!(
+            config.set("counts", "1,x"),
+            Err(ConfigError::BadValue { .. })
+        ));
+        assert_eq!(config, SimConfig::default());
+    }
+
+    #[test]
+    fn validate_rejects_out_of_range_values() {
+        assert!(SimConfig::default().validate(3).is_ok());
+
+        for (key, value) in [
+            ("tick_rate", "1e12"),
+            ("tick_rate", "1e-30"),
+            ("tick_rate", "0"),
+            ("arena_width", "inf"),
+            ("arena_width", "40"),
+            ("max_speed", "inf"),
+            ("sprite_size", "NaN"),
+            ("vision_max", "75"),
+            ("memory_secs", "-1"),
+            ("chase_speed", "1.5"),
+            ("fov", "0"),
+        ] {
+            let mut config = SimConfig::default();
+            config.set(key, value).unwrap();
+            assert!(
+                matches!(config.validate(3), Err(ConfigError::Invalid { key: k, .. }) if k == key),
+                "{key}={value} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn validate_checks_counts_against_the_factions() {
+        for (counts, ok) in [
+            ("", true),
+            ("10,0,5", true),
+            ("0,0", true),
+            ("10,10,10,10", false),
+            ("0,0,0", false),
+        ] {
+            let mut config = SimConfig::default();
+            config.set("counts", counts).unwrap();
+            assert_eq!(config.validate(3).is_ok(), ok, "counts={counts}");
+        }
+    }
+}
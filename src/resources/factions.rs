@@ -0,0 +1,206 @@
+use std::{fmt, fs, io, path::Path};
+
+use bevy::prelude::Resource;
+use serde::{Deserialize, Serialize};
+
+use crate::entities::Faction;
+
+/// A faction as declared in a factions file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FactionDef {
+    pub name: String,
+    pub sprite: String,
+    pub sound: String,
+    #[serde(default)]
+    pub tint: Option<(f32, f32, f32)>,
+    /// Volume of the conversion sound, relative to the master volume.
+    #[serde(default = "full_volume")]
+    pub volume: f64,
+    /// Names of the factions this one converts on contact.
+    pub beats: Vec<String>,
+}
+
+fn full_volume() -> f64 {
+    1.
+}
+
+#[derive(Deserialize)]
+struct FactionsFile {
+    factions: Vec<FactionDef>,
+}
+
+/// Every faction of the match and who beats whom, resolved once at startup.
+///
+/// The default is the classic Rock, Paper & Scissors cycle.
+#[derive(Resource, Debug, Clone)]
+pub struct Factions {
+    defs: Vec<FactionDef>,
+    targets: Vec<Vec<Faction>>,
+    enemies: Vec<Vec<Faction>>,
+}
+
+#[derive(Debug)]
+pub enum FactionsError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    TooFew(usize),
+    Duplicate(String),
+    Volume { faction: String, volume: f64 },
+    Unknown { faction: String, beats: String },
+    BeatsItself(String),
+    Mutual(String, String),
+}
+
+impl Factions {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FactionsError> {
+        let text = fs::read_to_string(path).map_err(FactionsError::Io)?;
+        Self::from_ron(&text)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, FactionsError> {
+        let file: FactionsFile = ron::from_str(text).map_err(FactionsError::Parse)?;
+        Self::new(file.factions)
+    }
+
+    pub fn new(defs: Vec<FactionDef>) -> Result<Self, FactionsError> {
+        if defs.len() < 2 {
+            return Err(FactionsError::TooFew(defs.len()));
+        }
+
+        for (i, def) in defs.iter().enumerate() {
+            if defs[..i].iter().any(|other| other.name == def.name) {
+                return Err(FactionsError::Duplicate(def.name.clone()));
+            }
+            if !(def.volume.is_finite() && def.volume >= 0.) {
+                return Err(FactionsError::Volume {
+                    faction: def.name.clone(),
+                    volume: def.volume,
+                });
+            }
+        }
+
+        let mut targets = vec![Vec::new(); defs.len()];
+        let mut enemies = vec![Vec::new(); defs.len()];
+
+        for (i, def) in defs.iter().enumerate() {
+            for name in def.beats.iter() {
+                let Some(target) = defs.iter().position(|other| &other.name == name) else {
+                    return Err(FactionsError::Unknown {
+                        faction: def.name.clone(),
+                        beats: name.clone(),
+                    });
+                };
+                if target == i {
+                    return Err(FactionsError::BeatsItself(def.name.clone()));
+                }
+                if defs[target].beats.contains(&def.name) {
+                    return Err(FactionsError::Mutual(def.name.clone(), name.clone()));
+                }
+                targets[i].push(Faction(target));
+                enemies[target].push(Faction(i));
+            }
+        }
+
+        Ok(Self {
+            defs,
+            targets,
+            enemies,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.defs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.defs.is_empty()
+    }
+
+    /// Declarations as read from the factions file, e.g. to store them in a
+    /// replay.
+    pub fn defs(&self) -> &[FactionDef] {
+        &self.defs
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Faction> {
+        (0..self.defs.len()).map(Faction)
+    }
+
+    pub fn def(&self, faction: Faction) -> &FactionDef {
+        &self.defs[faction.0]
+    }
+
+    pub fn name(&self, faction: Faction) -> &str {
+        &self.defs[faction.0].name
+    }
+
+    pub fn find(&self, name: &str) -> Option<Faction> {
+        self.defs
+            .iter()
+            .position(|def| def.name == name)
+            .map(Faction)
+    }
+
+    /// Factions `faction` chases and converts.
+    pub fn targets(&self, faction: Faction) -> &[Faction] {
+        &self.targets[faction.0]
+    }
+
+    /// Factions `faction` flees from.
+    pub fn enemies(&self, faction: Faction) -> &[Faction] {
+        &self.enemies[faction.0]
+    }
+
+    pub fn beats(&self, faction: Faction, other: Faction) -> bool {
+        self.targets[faction.0].contains(&other)
+    }
+}
+
+impl Default for Factions {
+    fn default() -> Self {
+        Self::from_ron(include_str!("../../assets/factions/classic.ron"))
+            .expect("the bundled factions file is valid")
+    }
+}
+
+impl fmt::Display for FactionsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "cannot read factions file: {e}"),
+            Self::Parse(e) => write!(f, "invalid factions file: {e}"),
+            Self::TooFew(n) => write!(f, "at least 2 factions are needed, found {n}"),
+            Self::Duplicate(name) => write!(f, "faction `{name}` is declared twice"),
+            Self::Volume { faction, volume } => write!(
+                f,
+                "`{faction}` volume cannot be negative or infinite, got {volume}"
+            ),
+            Self::Unknown { faction, beats } => {
+                write!(f, "`{faction}` beats `{beats}`, which is not a faction")
+            }
+            Self::BeatsItself(name) => write!(f, "`{name}` cannot beat itself"),
+            Self::Mutual(a, b) => write!(f, "`{a}` and `{b}` cannot beat each other"),
+        }
+    }
+}
+
+impl std::error::Error for FactionsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_bad_volumes() {
+        let defs = Factions::default().defs().to_vec();
+        assert!(Factions::new(defs.clone()).is_ok());
+
+        for volume in [-0.5, f64::NAN, f64::INFINITY] {
+            let mut defs = defs.clone();
+            defs[1].volume = volume;
+            assert!(matches!(
+                Factions::new(defs),
+                Err(FactionsError::Volume { .. })
+            ));
+        }
+    }
+}
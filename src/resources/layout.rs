@@ -0,0 +1,242 @@
+use std::{f32::consts::PI, fmt, str::FromStr};
+
+use bevy::math::{vec2, Vec2};
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::entities::Faction;
+
+use super::Region;
+
+/// How the entities of every faction are laid out at the start of a match.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpawnLayout {
+    /// Random circular regions, handed out to the factions in turn.
+    #[default]
+    Regions,
+    /// One cluster per faction, spread along the edges of the arena.
+    Corners,
+    /// One ring per faction around the center, the first one innermost.
+    Rings,
+    /// One vertical band per faction, from left to right.
+    Stripes,
+    /// A regular grid over the whole arena, factions shuffled.
+    Grid,
+    /// Evenly spaced random points over the whole arena, factions shuffled.
+    Poisson,
+    /// Uniformly random points over the whole arena, factions shuffled.
+    Mixed,
+}
+
+impl SpawnLayout {
+    pub const ALL: [Self; 7] = [
+        Self::Regions,
+        Self::Corners,
+        Self::Rings,
+        Self::Stripes,
+        Self::Grid,
+        Self::Poisson,
+        Self::Mixed,
+    ];
+
+    /// Where every entity starts, `counts[f]` of them for faction `f`.
+    ///
+    /// Points lie within `half` of the origin; `spacing` is roughly the room
+    /// an entity takes. `regions` is only used by [`SpawnLayout::Regions`].
+    pub fn points(
+        self,
+        counts: &[usize],
+        regions: &[Region],
+        half: Vec2,
+        spacing: f32,
+        rng: &mut impl Rng,
+    ) -> Vec<(Faction, Vec2)> {
+        let factions = counts.len();
+        let total: usize = counts.iter().sum();
+
+        match self {
+            Self::Regions => {
+                let mut points = Vec::with_capacity(total);
+                for (f, &count) in counts.iter().enumerate() {
+                    let mut own: Vec<_> = regions
+                        .iter()
+                        .filter(|region| region.faction == Faction(f))
+                        .collect();
+                    // more factions than regions
+                    if own.is_empty() {
+                        own = regions.iter().collect();
+                    }
+                    for region in own.iter().cycle().take(count) {
+                        points.push((Faction(f), region.center + in_disc(region.radius, rng)));
+                    }
+                }
+                points
+            }
+            Self::Corners => counts
+                .iter()
+                .enumerate()
+                .flat_map(|(f, &count)| {
+                    // from a direction to the edge of the square around it
+                    let angle = PI / 4. + 2. * PI * f as f32 / factions as f32;
+                    let direction = Vec2::from_angle(angle);
+                    let direction = direction / direction.x.abs().max(direction.y.abs());
+                    let radius = (spacing * (count as f32).sqrt()).min(half.min_element() / 2.);
+                    let center = direction * (half - radius);
+                    (0..count)
+                        .map(|_| (Faction(f), center + in_disc(radius, rng)))
+                        .collect::<Vec<_>>()
+                })
+                .collect(),
+            Self::Rings => counts
+                .iter()
+                .enumerate()
+                .flat_map(|(f, &count)| {
+                    let radius = half.min_element() * (f + 1) as f32 / factions as f32;
+                    let phase = rng.gen_range(0.0..1.);
+                    (0..count).map(move |i| {
+                        let angle = 2. * PI * (i as f32 + phase) / count as f32;
+                        (Faction(f), Vec2::from_angle(angle) * radius)
+                    })
+                })
+                .collect(),
+            Self::Stripes => counts
+                .iter()
+                .enumerate()
+                .flat_map(|(f, &count)| {
+                    let width = 2. * half.x / factions as f32;
+                    let left = -half.x + width * f as f32;
+                    (0..count)
+                        .map(|_| {
+                            let x = rng.gen_range(left..=left + width);
+                            let y = rng.gen_range(-half.y..=half.y);
+                            (Faction(f), vec2(x, y))
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect(),
+            Self::Grid => {
+                let columns = ((total as f32 * half.x / half.y).sqrt().ceil() as usize).max(1);
+                let rows = total.div_ceil(columns).max(1);
+                let cell = vec2(2. * half.x / columns as f32, 2. * half.y / rows as f32);
+                let points = (0..total).map(|i| {
+                    let (column, row) = ((i % columns) as f32, (i / columns) as f32);
+                    -half + cell * vec2(column + 0.5, row + 0.5)
+                });
+                shuffled(counts, points.collect(), rng)
+            }
+            Self::Poisson => {
+                // dart throwing, loosening the distance until everyone fits
+                let mut distance = (4. * half.x * half.y / total.max(1) as f32).sqrt() * 0.75;
+                let mut points: Vec<Vec2> = Vec::with_capacity(total);
+                let mut misses = 0;
+                while points.len() < total {
+                    let point = vec2(
+                        rng.gen_range(-half.x..=half.x),
+                        rng.gen_range(-half.y..=half.y),
+                    );
+                    if points.iter().all(|p| p.distance(point) >= distance) {
+                        points.push(point);
+                        misses = 0;
+                    } else {
+                        misses += 1;
+                        if misses == 30 {
+                            distance *= 0.9;
+                            misses = 0;
+                        }
+                    }
+                }
+                shuffled(counts, points, rng)
+            }
+            Self::Mixed => {
+                let points = (0..total)
+                    .map(|_| {
+                        vec2(
+                            rng.gen_range(-half.x..=half.x),
+                            rng.gen_range(-half.y..=half.y),
+                        )
+                    })
+                    .collect();
+                shuffled(counts, points, rng)
+            }
+        }
+    }
+}
+
+fn in_disc(radius: f32, rng: &mut impl Rng) -> Vec2 {
+    let angle = rng.gen_range(0.0..(2.0 * PI));
+    Vec2::from_angle(angle) * rng.gen_range(0.0..radius)
+}
+
+// hands `points` out to the factions in a random order
+fn shuffled(counts: &[usize], points: Vec<Vec2>, rng: &mut impl Rng) -> Vec<(Faction, Vec2)> {
+    let mut factions: Vec<Faction> = counts
+        .iter()
+        .enumerate()
+        .flat_map(|(f, &count)| std::iter::repeat_n(Faction(f), count))
+        .collect();
+    factions.shuffle(rng);
+    factions.into_iter().zip(points).collect()
+}
+
+impl fmt::Display for SpawnLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Regions => "regions",
+            Self::Corners => "corners",
+            Self::Rings => "rings",
+            Self::Stripes => "stripes",
+            Self::Grid => "grid",
+            Self::Poisson => "poisson",
+            Self::Mixed => "mixed",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for SpawnLayout {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        Self::ALL
+            .into_iter()
+            .find(|layout| layout.to_string().eq_ignore_ascii_case(s.trim()))
+            .ok_or(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn layouts_place_everyone_inside() {
+        let counts = [7, 12, 0, 1];
+        let half = vec2(400., 300.);
+        let regions: Vec<_> = (0..counts.len())
+            .map(|f| Region {
+                center: vec2(-300. + 200. * f as f32, 0.),
+                radius: 50.,
+                faction: Faction(f),
+            })
+            .collect();
+
+        for layout in SpawnLayout::ALL {
+            let mut rng = StdRng::seed_from_u64(3);
+            let points = layout.points(&counts, &regions, half, 20., &mut rng);
+
+            for (f, &count) in counts.iter().enumerate() {
+                let placed = points.iter().filter(|(faction, _)| *faction == Faction(f));
+                assert_eq!(placed.count(), count, "{layout}, faction {f}");
+            }
+            assert_eq!(points.len(), counts.iter().sum::<usize>(), "{layout}");
+            for (_, point) in points {
+                assert!(
+                    point.abs().cmple(half).all(),
+                    "{layout}: {point} is outside"
+                );
+            }
+        }
+    }
+}
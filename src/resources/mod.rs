@@ -1,55 +1,228 @@
-use bevy::prelude::{Entity, Resource, States};
+pub mod config;
+pub mod factions;
+pub mod layout;
+pub mod obstacle;
+pub mod replay;
+pub mod scenario;
+
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use bevy::{
+    math::{vec2, Vec2},
+    prelude::{Entity, Resource, States},
+};
+
+use crate::{
+    constants::{ARENA_HEIGHT, ARENA_WIDTH},
+    entities::Faction,
+};
+
+use self::{config::SimConfig, factions::Factions};
+
+/// A circular area entities of a single faction spawn in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Region {
+    pub center: Vec2,
+    pub radius: f32,
+    pub faction: Faction,
+}
 
 #[derive(Resource, Default)]
-pub struct GenerableRegions(pub Vec<(f32, f32, f32)>);
+pub struct GenerableRegions(pub Vec<Region>);
 
 #[derive(Resource, Default)]
 pub struct CollidablePairs(pub Vec<(Entity, Entity)>);
 
-#[derive(States, Default, Debug, Clone, PartialEq, Eq, Hash)]
+/// Seed every random draw of the match comes from.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct Seed(pub u64);
+
+/// Size of the playing field, centered on the origin.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct Arena {
+    pub width: f32,
+    pub height: f32,
+}
+
+// room kept clear along the edges, and how close entities may get to it
+const BORDER: f32 = 38.;
+const BORDER_GAP: f32 = 5.;
+
+impl Arena {
+    /// Sides this short or shorter leave no room inside the bounds.
+    pub const MIN_SIDE: f32 = BORDER + 2. * BORDER_GAP;
+
+    /// Half extents of the area `check_boundaries` keeps entities in.
+    pub fn bounds(&self) -> Vec2 {
+        vec2(self.width - BORDER, self.height - BORDER) / 2. - BORDER_GAP
+    }
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self {
+            width: ARENA_WIDTH,
+            height: ARENA_HEIGHT,
+        }
+    }
+}
+
+impl From<&SimConfig> for Arena {
+    fn from(config: &SimConfig) -> Self {
+        Self {
+            width: config.arena_width,
+            height: config.arena_height,
+        }
+    }
+}
+
+#[derive(States, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum GameState {
     #[default]
     LoadingRes,
     InGame,
     Paused,
+    /// Paused, with the mouse placing and removing entities.
+    Editor,
+    GameOver,
+}
+
+#[derive(Resource, Default)]
+pub struct MatchStats {
+    pub ticks: u32,
+    pub elapsed: Duration,
+    pub conversions: u32,
+}
+
+/// Population of every faction, sampled once per simulation tick.
+#[derive(Resource, Default)]
+pub struct PopulationHistory {
+    pub samples: Vec<(Duration, Vec<usize>)>,
+}
+
+impl PopulationHistory {
+    pub fn write_csv(&self, factions: &Factions, mut out: impl Write) -> io::Result<()> {
+        write!(out, "time")?;
+        for faction in factions.iter() {
+            write!(out, ",{}", factions.name(faction))?;
+        }
+        writeln!(out)?;
+
+        for (time, counts) in self.samples.iter() {
+            write!(out, "{:.4}", time.as_secs_f64())?;
+            for count in counts {
+                write!(out, ",{count}")?;
+            }
+            writeln!(out)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Where [`PopulationHistory`] is dumped, on demand or when a match ends.
+#[derive(Resource, Clone, Debug)]
+pub struct CsvExport {
+    pub path: PathBuf,
+    pub on_game_over: bool,
+}
+
+impl Default for CsvExport {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("population.csv"),
+            on_game_over: false,
+        }
+    }
+}
+
+/// Where the world is saved as a [`scenario::Scenario`], on demand or as
+/// soon as a match starts.
+#[derive(Resource, Clone, Debug)]
+pub struct ScenarioExport {
+    pub path: PathBuf,
+    pub on_start: bool,
+}
+
+impl Default for ScenarioExport {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("scenario.ron"),
+            on_start: false,
+        }
+    }
 }
 
 #[derive(Resource)]
 pub struct DebugState {
     pub points: bool,
-    pub rocks: bool,
-    pub papers: bool,
-    pub scissors: bool,
-    pub radius_rocks: bool,
-    pub radius_papers: bool,
-    pub radius_scissors: bool,
+    pub hud: bool,
+    /// Outline every entity in the color of its [`crate::entities::Behavior`].
+    pub behavior: bool,
+    /// Show a bar with the [`crate::entities::Energy`] left above every
+    /// entity.
+    pub energy: bool,
+    pub hidden: HashSet<Faction>,
+    pub radius: HashSet<Faction>,
+}
+
+/// Sandbox editor settings, see [`GameState::Editor`].
+#[derive(Resource, Default)]
+pub struct Editor {
+    /// Faction new entities join.
+    pub faction: Faction,
+    /// Paint and erase many entities at once instead of one at a time.
+    pub brush: bool,
 }
 
 #[derive(Resource)]
 pub struct GameControl {
     pub stop: bool,
     pub sound: bool,
+    /// Master volume, from 0 to 1.
+    pub volume: f64,
+    /// Pan conversion sounds by where they happen.
+    pub panning: bool,
+    /// Playback speed, one of [`GameControl::SPEEDS`].
+    pub speed: f64,
+    /// Run a single tick while paused.
+    pub step: bool,
+    /// Run ticks back to back, without rendering, until the match is over.
+    pub skip: bool,
 }
 
-impl Default for GameControl {
+impl GameControl {
+    pub const SPEEDS: [f64; 7] = [0.25, 0.5, 1., 2., 4., 8., 16.];
+}
+
+impl Default for DebugState {
     fn default() -> Self {
         Self {
-            stop: true,
-            sound: true,
+            points: false,
+            hud: true,
+            behavior: false,
+            energy: false,
+            hidden: HashSet::new(),
+            radius: HashSet::new(),
         }
     }
 }
 
-impl Default for DebugState {
+impl Default for GameControl {
     fn default() -> Self {
         Self {
-            points: false,
-            rocks: true,
-            papers: true,
-            scissors: true,
-            radius_rocks: false,
-            radius_papers: false,
-            radius_scissors: false,
+            stop: true,
+            sound: true,
+            volume: 1.,
+            panning: false,
+            speed: 1.,
+            step: false,
+            skip: false,
         }
     }
 }
@@ -0,0 +1,380 @@
+use bevy::{
+    math::{vec2, Vec2},
+    prelude::Resource,
+};
+use serde::{Deserialize, Serialize};
+
+/// A static shape entities cannot walk through nor see through. Coordinates
+/// are in pixels from the center of the arena.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Obstacle {
+    Rect {
+        center: (f32, f32),
+        size: (f32, f32),
+    },
+    Circle {
+        center: (f32, f32),
+        radius: f32,
+    },
+    /// A simple (not self-intersecting) polygon, in either winding order.
+    Polygon {
+        points: Vec<(f32, f32)>,
+    },
+}
+
+/// Every obstacle of the current match, from the config and the scenario.
+#[derive(Resource, Default, Debug, Clone)]
+pub struct Obstacles(pub Vec<Obstacle>);
+
+impl Obstacle {
+    /// Corners of a rectangle or polygon, `None` for a circle.
+    pub fn corners(&self) -> Option<Vec<Vec2>> {
+        match self {
+            Self::Rect { center, size } => {
+                let (center, half) = (Vec2::from(*center), Vec2::from(*size) / 2.);
+                Some(vec![
+                    center + vec2(-half.x, -half.y),
+                    center + vec2(half.x, -half.y),
+                    center + vec2(half.x, half.y),
+                    center + vec2(-half.x, half.y),
+                ])
+            }
+            Self::Circle { .. } => None,
+            Self::Polygon { points } => Some(points.iter().copied().map(Vec2::from).collect()),
+        }
+    }
+
+    /// Whether it covers any ground at all; shapes that do not (or whose
+    /// coordinates are not finite) have nothing to push entities out of.
+    pub fn has_area(&self) -> bool {
+        match (self, self.corners()) {
+            (Self::Circle { center, radius }, _) => {
+                Vec2::from(*center).is_finite() && radius.is_finite() && *radius > 0.
+            }
+            (_, Some(corners)) => {
+                let area: f32 = edges(&corners).map(|(a, b)| a.perp_dot(b)).sum();
+                corners.iter().all(|corner| corner.is_finite()) && area.is_finite() && area != 0.
+            }
+            _ => false,
+        }
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        match (self, self.corners()) {
+            (Self::Circle { center, radius }, _) => point.distance(Vec2::from(*center)) < *radius,
+            (_, Some(corners)) => {
+                // even-odd rule
+                let mut inside = false;
+                for (a, b) in edges(&corners) {
+                    if (a.y > point.y) != (b.y > point.y)
+                        && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x)
+                    {
+                        inside = !inside;
+                    }
+                }
+                inside
+            }
+            _ => false,
+        }
+    }
+
+    /// Point of the outline closest to `point`.
+    pub fn closest_edge(&self, point: Vec2) -> Vec2 {
+        match (self, self.corners()) {
+            (Self::Circle { center, radius }, _) => {
+                let center = Vec2::from(*center);
+                center + (point - center).normalize_or(Vec2::X) * *radius
+            }
+            (_, Some(corners)) => edges(&corners)
+                .map(|(a, b)| closest_on_segment(point, a, b))
+                .min_by(|p, q| {
+                    p.distance_squared(point)
+                        .total_cmp(&q.distance_squared(point))
+                })
+                .unwrap_or(point),
+            _ => point,
+        }
+    }
+
+    /// Whether the segment from `from` to `to` crosses the obstacle.
+    pub fn blocks(&self, from: Vec2, to: Vec2) -> bool {
+        match (self, self.corners()) {
+            (Self::Circle { center, radius }, _) => {
+                closest_on_segment(Vec2::from(*center), from, to).distance(Vec2::from(*center))
+                    < *radius
+            }
+            (_, Some(corners)) => {
+                self.contains(from)
+                    || self.contains(to)
+                    || edges(&corners).any(|(a, b)| segments_cross(from, to, a, b))
+            }
+            _ => false,
+        }
+    }
+
+    /// Where a disc of `radius` at `pos` has to move so it no longer
+    /// overlaps the obstacle, with the direction it was pushed in.
+    pub fn push_out(&self, pos: Vec2, radius: f32) -> Option<(Vec2, Vec2)> {
+        let edge = self.closest_edge(pos);
+        let inside = self.contains(pos);
+        if !inside && pos.distance(edge) >= radius {
+            return None;
+        }
+
+        let away = if inside { edge - pos } else { pos - edge };
+        let normal = away.normalize_or(Vec2::X);
+        Some((edge + normal * radius, normal))
+    }
+}
+
+impl Obstacles {
+    /// Whether nothing stands between `from` and `to`.
+    pub fn in_sight(&self, from: Vec2, to: Vec2) -> bool {
+        !self.0.iter().any(|obstacle| obstacle.blocks(from, to))
+    }
+
+    /// `pos` moved out of every obstacle a disc of `radius` overlaps, with
+    /// the sum of the directions it was pushed in.
+    pub fn push_out(&self, mut pos: Vec2, radius: f32) -> (Vec2, Vec2) {
+        let mut pushed = Vec2::ZERO;
+        for obstacle in self.0.iter() {
+            if let Some((moved, normal)) = obstacle.push_out(pos, radius) {
+                pos = moved;
+                pushed += normal;
+            }
+        }
+        (pos, pushed)
+    }
+}
+
+fn edges(corners: &[Vec2]) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+    corners
+        .iter()
+        .zip(corners.iter().cycle().skip(1))
+        .map(|(&a, &b)| (a, b))
+}
+
+/// Point of the segment from `a` to `b` closest to `point`.
+pub fn closest_on_segment(point: Vec2, a: Vec2, b: Vec2) -> Vec2 {
+    let ab = b - a;
+    let t = ((point - a).dot(ab) / ab.length_squared().max(f32::EPSILON)).clamp(0., 1.);
+    a + ab * t
+}
+
+fn segments_cross(p1: Vec2, p2: Vec2, q1: Vec2, q2: Vec2) -> bool {
+    let d1 = (q2 - q1).perp_dot(p1 - q1);
+    let d2 = (q2 - q1).perp_dot(p2 - q1);
+    let d3 = (p2 - p1).perp_dot(q1 - p1);
+    let d4 = (p2 - p1).perp_dot(q2 - p1);
+    (d1 > 0.) != (d2 > 0.) && (d3 > 0.) != (d4 > 0.)
+}
+
+/// Triangles covering a simple polygon, as indices into `corners`, by ear
+/// clipping.
+pub fn triangulate(corners: &[Vec2]) -> Vec<[usize; 3]> {
+    let mut remaining: Vec<usize> = (0..corners.len()).collect();
+    // counter-clockwise makes every ear turn left
+    let area: f32 = edges(corners).map(|(a, b)| a.perp_dot(b)).sum();
+    if area < 0. {
+        remaining.reverse();
+    }
+
+    let mut triangles = Vec::new();
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = (
+                corners[remaining[(i + n - 1) % n]],
+                corners[remaining[i]],
+                corners[remaining[(i + 1) % n]],
+            );
+            (b - a).perp_dot(c - b) > 0.
+                && remaining.iter().all(|&j| {
+                    let p = corners[j];
+                    p == a || p == b || p == c || !in_triangle(p, a, b, c)
+                })
+        });
+        // degenerate outline, give up on what is left
+        let Some(i) = ear else {
+            break;
+        };
+        triangles.push([
+            remaining[(i + n - 1) % n],
+            remaining[i],
+            remaining[(i + 1) % n],
+        ]);
+        remaining.remove(i);
+    }
+    if let [a, b, c] = remaining[..] {
+        triangles.push([a, b, c]);
+    }
+    triangles
+}
+
+fn in_triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    let d1 = (b - a).perp_dot(p - a);
+    let d2 = (c - b).perp_dot(p - b);
+    let d3 = (a - c).perp_dot(p - c);
+    d1 >= 0. && d2 >= 0. && d3 >= 0.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square() -> Obstacle {
+        Obstacle::Rect {
+            center: (0., 0.),
+            size: (20., 20.),
+        }
+    }
+
+    // an L, to catch concave corners
+    fn ell() -> Obstacle {
+        Obstacle::Polygon {
+            points: vec![
+                (0., 0.),
+                (30., 0.),
+                (30., 10.),
+                (10., 10.),
+                (10., 30.),
+                (0., 30.),
+            ],
+        }
+    }
+
+    fn area(corners: &[Vec2], triangles: &[[usize; 3]]) -> f32 {
+        triangles
+            .iter()
+            .map(|&[a, b, c]| (corners[b] - corners[a]).perp_dot(corners[c] - corners[a]) / 2.)
+            .sum()
+    }
+
+    #[test]
+    fn empty_shapes_have_no_area() {
+        assert!(square().has_area());
+        assert!(ell().has_area());
+        assert!(!Obstacle::Polygon { points: vec![] }.has_area());
+        assert!(!Obstacle::Polygon {
+            points: vec![(0., 0.), (10., 10.), (20., 20.)],
+        }
+        .has_area());
+        assert!(!Obstacle::Rect {
+            center: (0., 0.),
+            size: (0., 10.),
+        }
+        .has_area());
+        assert!(!Obstacle::Circle {
+            center: (0., 0.),
+            radius: 0.,
+        }
+        .has_area());
+    }
+
+    #[test]
+    fn shapes_off_the_number_line_have_no_area() {
+        assert!(!Obstacle::Polygon {
+            points: vec![(0., 0.), (10., 0.), (f32::NAN, 10.)],
+        }
+        .has_area());
+        assert!(!Obstacle::Rect {
+            center: (0., 0.),
+            size: (f32::INFINITY, 10.),
+        }
+        .has_area());
+        assert!(!Obstacle::Circle {
+            center: (f32::NAN, 0.),
+            radius: 10.,
+        }
+        .has_area());
+        assert!(!Obstacle::Circle {
+            center: (0., 0.),
+            radius: f32::NAN,
+        }
+        .has_area());
+        assert!(!Obstacle::Circle {
+            center: (0., 0.),
+            radius: f32::INFINITY,
+        }
+        .has_area());
+    }
+
+    #[test]
+    fn contains_points_inside_only() {
+        assert!(square().contains(vec2(5., -5.)));
+        assert!(!square().contains(vec2(15., 0.)));
+        assert!(ell().contains(vec2(5., 25.)));
+        assert!(!ell().contains(vec2(20., 20.)));
+        let circle = Obstacle::Circle {
+            center: (10., 0.),
+            radius: 5.,
+        };
+        assert!(circle.contains(vec2(12., 2.)));
+        assert!(!circle.contains(vec2(0., 0.)));
+    }
+
+    #[test]
+    fn segments_cross_only_when_they_intersect() {
+        let (a, b) = (vec2(-1., 0.), vec2(1., 0.));
+        assert!(segments_cross(a, b, vec2(0., -1.), vec2(0., 1.)));
+        assert!(!segments_cross(a, b, vec2(2., -1.), vec2(2., 1.)));
+        assert!(!segments_cross(a, b, vec2(-1., 1.), vec2(1., 1.)));
+    }
+
+    #[test]
+    fn blocks_sight_through_but_not_around() {
+        let (left, right) = (vec2(-30., 0.), vec2(30., 0.));
+        assert!(square().blocks(left, right));
+        assert!(!square().blocks(vec2(-30., 15.), vec2(30., 15.)));
+        // the notch of the L is open
+        assert!(!ell().blocks(vec2(20., 40.), vec2(20., 15.)));
+        assert!(ell().blocks(vec2(20., 40.), vec2(20., 5.)));
+
+        let circle = Obstacle::Circle {
+            center: (0., 0.),
+            radius: 10.,
+        };
+        assert!(circle.blocks(left, right));
+        assert!(!circle.blocks(vec2(-30., 11.), vec2(30., 11.)));
+
+        let obstacles = Obstacles(vec![square()]);
+        assert!(!obstacles.in_sight(left, right));
+        assert!(Obstacles::default().in_sight(left, right));
+    }
+
+    #[test]
+    fn push_out_moves_discs_clear_of_the_edge() {
+        // far enough already
+        assert_eq!(square().push_out(vec2(20., 0.), 5.), None);
+
+        let (pos, normal) = square().push_out(vec2(12., 0.), 5.).unwrap();
+        assert_eq!(normal, Vec2::X);
+        assert!((pos - vec2(15., 0.)).length() < 1e-4);
+
+        // from inside, out through the closest edge
+        let (pos, normal) = square().push_out(vec2(0., 8.), 5.).unwrap();
+        assert_eq!(normal, Vec2::Y);
+        assert!((pos - vec2(0., 15.)).length() < 1e-4);
+
+        let obstacles = Obstacles(vec![square()]);
+        let (pos, pushed) = obstacles.push_out(vec2(-12., 0.), 5.);
+        assert_eq!(pushed, Vec2::NEG_X);
+        assert!((pos - vec2(-15., 0.)).length() < 1e-4);
+    }
+
+    #[test]
+    fn triangulate_covers_the_polygon() {
+        let corners = ell().corners().unwrap();
+        let triangles = triangulate(&corners);
+        assert_eq!(triangles.len(), corners.len() - 2);
+        assert!((area(&corners, &triangles) - 500.).abs() < 1e-3);
+
+        // either winding order
+        let reversed: Vec<Vec2> = corners.iter().rev().copied().collect();
+        let triangles = triangulate(&reversed);
+        assert_eq!(triangles.len(), reversed.len() - 2);
+        assert!((area(&reversed, &triangles).abs() - 500.).abs() < 1e-3);
+
+        assert!(triangulate(&[]).is_empty());
+    }
+}
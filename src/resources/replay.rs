@@ -0,0 +1,122 @@
+use std::{fmt, fs, io, path::Path};
+
+use bevy::prelude::Resource;
+use serde::{Deserialize, Serialize};
+
+use super::{config::SimConfig, factions::FactionDef, scenario::Scenario};
+
+/// Bumped whenever the layout of [`Replay`] changes.
+pub const REPLAY_VERSION: u32 = 1;
+
+/// Ticks between two position keyframes.
+pub const KEYFRAME_TICKS: u32 = 60;
+
+/// Everything needed to play a match again: the match is re-simulated from
+/// its seed, config and factions, while conversions and keyframes tell
+/// whether the re-simulation still matches what was recorded.
+#[derive(Resource, Serialize, Deserialize, Debug, Clone)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub config: SimConfig,
+    pub factions: Vec<FactionDef>,
+    /// Starting world, when the match did not start from a random layout.
+    #[serde(default)]
+    pub scenario: Option<Scenario>,
+    /// Ticks played until the match ended (or the recording stopped).
+    pub ticks: u32,
+    pub winner: Option<usize>,
+    pub conversions: Vec<ConversionRecord>,
+    pub keyframes: Vec<Keyframe>,
+}
+
+/// A conversion, entities being referred to by their [`crate::entities::Id`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ConversionRecord {
+    pub tick: u32,
+    pub actor: u32,
+    pub target: u32,
+    pub faction: usize,
+}
+
+/// Faction and position of every entity, in `Id` order, after `tick`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Keyframe {
+    pub tick: u32,
+    pub entities: Vec<(usize, f32, f32)>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Write(ron::Error),
+    Version(u32),
+}
+
+impl Replay {
+    pub fn new(
+        seed: u64,
+        config: SimConfig,
+        factions: Vec<FactionDef>,
+        scenario: Option<Scenario>,
+    ) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            seed,
+            config,
+            factions,
+            scenario,
+            ticks: 0,
+            winner: None,
+            conversions: Vec::new(),
+            keyframes: Vec::new(),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        let text = fs::read_to_string(path).map_err(ReplayError::Io)?;
+        let replay: Self = ron::from_str(&text).map_err(ReplayError::Parse)?;
+        if replay.version != REPLAY_VERSION {
+            return Err(ReplayError::Version(replay.version));
+        }
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        // one keyframe per line keeps the file compact yet diffable
+        let pretty = ron::ser::PrettyConfig::new().depth_limit(2);
+        let text = ron::ser::to_string_pretty(self, pretty).map_err(ReplayError::Write)?;
+        fs::write(path, text).map_err(ReplayError::Io)
+    }
+
+    /// Conversions recorded during `tick`.
+    pub fn conversions_at(&self, tick: u32) -> &[ConversionRecord] {
+        let start = self.conversions.partition_point(|c| c.tick < tick);
+        let end = self.conversions.partition_point(|c| c.tick <= tick);
+        &self.conversions[start..end]
+    }
+
+    pub fn keyframe_at(&self, tick: u32) -> Option<&Keyframe> {
+        self.keyframes
+            .binary_search_by_key(&tick, |k| k.tick)
+            .ok()
+            .map(|i| &self.keyframes[i])
+    }
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "cannot access replay file: {e}"),
+            Self::Parse(e) => write!(f, "invalid replay file: {e}"),
+            Self::Write(e) => write!(f, "cannot encode replay: {e}"),
+            Self::Version(found) => write!(
+                f,
+                "replay file version {found} is not supported (expected {REPLAY_VERSION})"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
@@ -0,0 +1,188 @@
+use std::{fmt, fs, io, path::Path};
+
+use bevy::prelude::Resource;
+use serde::{Deserialize, Serialize};
+
+use super::{factions::Factions, obstacle::Obstacle, Arena};
+
+/// Bumped whenever the layout of [`Scenario`] changes.
+pub const SCENARIO_VERSION: u32 = 1;
+
+/// A hand-made (or saved) starting world, spawned instead of the random
+/// layout of [`super::config::SimConfig`].
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Scenario {
+    pub version: u32,
+    pub entities: Vec<ScenarioEntity>,
+    /// Added to the obstacles of the config.
+    #[serde(default)]
+    pub obstacles: Vec<Obstacle>,
+}
+
+/// An entity as it starts, its faction referred to by name so that the file
+/// stays valid if factions are reordered.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScenarioEntity {
+    pub faction: String,
+    pub position: (f32, f32),
+    /// Full vision radius, sprite included.
+    pub vision: f32,
+    #[serde(default)]
+    pub velocity: (f32, f32),
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Write(ron::Error),
+    Version(u32),
+    UnknownFaction(String),
+    BadVision(f32),
+    OutOfBounds((f32, f32)),
+    EmptyObstacle(Obstacle),
+}
+
+impl Scenario {
+    pub fn new(entities: Vec<ScenarioEntity>, obstacles: Vec<Obstacle>) -> Self {
+        Self {
+            version: SCENARIO_VERSION,
+            entities,
+            obstacles,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ScenarioError> {
+        let text = fs::read_to_string(path).map_err(ScenarioError::Io)?;
+        let scenario: Self = ron::from_str(&text).map_err(ScenarioError::Parse)?;
+        if scenario.version != SCENARIO_VERSION {
+            return Err(ScenarioError::Version(scenario.version));
+        }
+        Ok(scenario)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ScenarioError> {
+        // one entity per line
+        let pretty = ron::ser::PrettyConfig::new().depth_limit(2);
+        let text = ron::ser::to_string_pretty(self, pretty).map_err(ScenarioError::Write)?;
+        fs::write(path, text).map_err(ScenarioError::Io)
+    }
+
+    /// Every entity must belong to one of `factions`, see a finite,
+    /// non-negative distance and start inside `arena`, and every obstacle
+    /// must cover some ground.
+    pub fn check(&self, factions: &Factions, arena: &Arena) -> Result<(), ScenarioError> {
+        let bounds = arena.bounds();
+        for entity in &self.entities {
+            if factions.find(&entity.faction).is_none() {
+                return Err(ScenarioError::UnknownFaction(entity.faction.clone()));
+            }
+            if !(entity.vision.is_finite() && entity.vision >= 0.) {
+                return Err(ScenarioError::BadVision(entity.vision));
+            }
+            // written so that NaN fails too
+            let (x, y) = entity.position;
+            if !(x.abs() <= bounds.x && y.abs() <= bounds.y) {
+                return Err(ScenarioError::OutOfBounds(entity.position));
+            }
+        }
+        match self.obstacles.iter().find(|obstacle| !obstacle.has_area()) {
+            Some(obstacle) => Err(ScenarioError::EmptyObstacle(obstacle.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "cannot access scenario file: {e}"),
+            Self::Parse(e) => write!(f, "invalid scenario file: {e}"),
+            Self::Write(e) => write!(f, "cannot encode scenario: {e}"),
+            Self::Version(found) => write!(
+                f,
+                "scenario file version {found} is not supported (expected {SCENARIO_VERSION})"
+            ),
+            Self::UnknownFaction(name) => write!(f, "`{name}` is not a faction"),
+            Self::BadVision(vision) => {
+                write!(f, "vision cannot be negative or infinite, got {vision}")
+            }
+            Self::OutOfBounds((x, y)) => write!(f, "({x}, {y}) is outside the arena"),
+            Self::EmptyObstacle(obstacle) => write!(f, "{obstacle:?} has no area"),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(faction: &str, position: (f32, f32), vision: f32) -> ScenarioEntity {
+        ScenarioEntity {
+            faction: faction.to_owned(),
+            position,
+            vision,
+            velocity: (0., 0.),
+        }
+    }
+
+    #[test]
+    fn saves_and_loads_the_same_scenario() {
+        let scenario = Scenario::new(
+            vec![
+                entity("Rock", (-120.5, 33.25), 61.),
+                ScenarioEntity {
+                    velocity: (1.5, -0.1),
+                    ..entity("Paper", (0.1, -0.2), 48.75)
+                },
+            ],
+            vec![
+                Obstacle::Rect {
+                    center: (10., 20.),
+                    size: (30., 40.),
+                },
+                Obstacle::Circle {
+                    center: (-50., 0.),
+                    radius: 12.5,
+                },
+                Obstacle::Polygon {
+                    points: vec![(0., 0.), (10., 0.), (0., 10.)],
+                },
+            ],
+        );
+        let path = std::env::temp_dir().join(format!("scenario-{}.ron", std::process::id()));
+
+        scenario.save(&path).unwrap();
+        let loaded = Scenario::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), scenario);
+    }
+
+    #[test]
+    fn check_rejects_bad_entities() {
+        let factions = Factions::default();
+        let arena = Arena::default();
+        let edge = arena.bounds().x;
+        let check = |entity| Scenario::new(vec![entity], Vec::new()).check(&factions, &arena);
+
+        assert!(check(entity("Rock", (edge, 0.), 0.)).is_ok());
+        assert!(matches!(
+            check(entity("Stone", (0., 0.), 50.)),
+            Err(ScenarioError::UnknownFaction(_))
+        ));
+        for vision in [-1., f32::NAN, f32::INFINITY] {
+            assert!(matches!(
+                check(entity("Rock", (0., 0.), vision)),
+                Err(ScenarioError::BadVision(_))
+            ));
+        }
+        for position in [(edge + 1., 0.), (0., -1e6), (f32::NAN, 0.)] {
+            assert!(matches!(
+                check(entity("Rock", position, 50.)),
+                Err(ScenarioError::OutOfBounds(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use std::{
+    io::{self, Write},
+    thread,
+};
+
+use crate::{
+    cli::SweepArgs,
+    resources::{
+        config::{ConfigError, SimConfig},
+        factions::Factions,
+    },
+    tournament::{play_matches, win_rates, write_file, MatchResult},
+};
+
+/// Aggregated results of the matches played with one combination of values.
+struct Cell {
+    values: Vec<String>,
+    /// `None` when the combination is not a valid config.
+    stats: Option<CellStats>,
+}
+
+struct CellStats {
+    matches: usize,
+    undecided: usize,
+    fairness: f64,
+    /// `None` when every match hit the tick limit.
+    mean_seconds: Option<f64>,
+    win_rates: Vec<f64>,
+}
+
+/// 1 when every faction wins equally often, 0 when the same one always wins.
+pub fn fairness(wins: &[usize]) -> f64 {
+    let total: usize = wins.iter().sum();
+    if total == 0 || wins.len() < 2 {
+        return 0.;
+    }
+
+    let even = 1. / wins.len() as f64;
+    let spread: f64 = wins
+        .iter()
+        .map(|&w| (w as f64 / total as f64 - even).abs())
+        .sum();
+    1. - spread / (2. * (1. - even))
+}
+
+/// Mean length of the matches that ended with a winner; the ones cut short
+/// by the tick limit are only counted as undecided.
+fn mean_seconds(results: &[MatchResult]) -> Option<f64> {
+    let decided: Vec<f64> = results
+        .iter()
+        .filter(|r| r.winner.is_some())
+        .map(|r| r.seconds as f64)
+        .collect();
+    (!decided.is_empty()).then(|| decided.iter().sum::<f64>() / decided.len() as f64)
+}
+
+/// Every combination of the values to vary, first key varying slowest.
+fn grid(vary: &[(String, Vec<String>)]) -> Vec<Vec<String>> {
+    vary.iter()
+        .fold(vec![Vec::new()], |combinations, (_, values)| {
+            combinations
+                .iter()
+                .flat_map(|prefix| {
+                    values.iter().map(move |value| {
+                        let mut combination = prefix.clone();
+                        combination.push(value.clone());
+                        combination
+                    })
+                })
+                .collect()
+        })
+}
+
+fn cell_config(
+    base: &SimConfig,
+    vary: &[(String, Vec<String>)],
+    values: &[String],
+) -> Result<SimConfig, ConfigError> {
+    let mut config = base.clone();
+    for ((key, _), value) in vary.iter().zip(values) {
+        config.set(key, value)?;
+    }
+    Ok(config)
+}
+
+pub fn run(args: &SweepArgs, factions: &Factions, base: &SimConfig, max_ticks: u32) {
+    let jobs = args
+        .jobs
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+
+    // typos in keys or values should not wait for hours of matches
+    let combinations = grid(&args.vary);
+    let mut configs = Vec::new();
+    for values in combinations.iter() {
+        match cell_config(base, &args.vary, values) {
+            Ok(config) => configs.push(config),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let mut cells = Vec::new();
+    for (values, config) in combinations.into_iter().zip(configs) {
+        let label = label(&args.vary, &values);
+        if let Err(e) = config.validate(factions.len()) {
+            println!("{label}: skipped, {e}");
+            cells.push(Cell {
+                values,
+                stats: None,
+            });
+            continue;
+        }
+
+        let results = play_matches(
+            args.first_seed,
+            args.seeds,
+            jobs,
+            factions,
+            &config,
+            max_ticks,
+        );
+        let rates = win_rates(factions, &results);
+        let wins: Vec<usize> = rates.iter().map(|rate| rate.wins).collect();
+        let stats = CellStats {
+            matches: results.len(),
+            undecided: results.iter().filter(|r| r.winner.is_none()).count(),
+            fairness: fairness(&wins),
+            mean_seconds: mean_seconds(&results),
+            win_rates: rates.iter().map(|rate| rate.rate).collect(),
+        };
+        let length = match stats.mean_seconds {
+            Some(seconds) => format!("{seconds:.1}s"),
+            None => "-".to_owned(),
+        };
+        println!(
+            "{label}: fairness {:.2}, mean length {length}, {} undecided",
+            stats.fairness, stats.undecided
+        );
+        cells.push(Cell {
+            values,
+            stats: Some(stats),
+        });
+    }
+
+    println!();
+    print_heat_maps(&args.vary, &cells);
+
+    if let Some(path) = &args.csv {
+        write_file(path, |out| write_csv(&args.vary, factions, &cells, out));
+    }
+}
+
+fn label(vary: &[(String, Vec<String>)], values: &[String]) -> String {
+    vary.iter()
+        .zip(values)
+        .map(|((key, _), value)| format!("{key}={value}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+const SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
+
+// darker is higher, relative to the range of the table
+fn shade(value: f64, min: f64, max: f64) -> char {
+    let t = if max > min {
+        (value - min) / (max - min)
+    } else {
+        1.
+    };
+    SHADES[(t * (SHADES.len() - 1) as f64).round() as usize]
+}
+
+type Metric = fn(&CellStats) -> Option<f64>;
+
+fn print_heat_maps(vary: &[(String, Vec<String>)], cells: &[Cell]) {
+    let tables: [(&str, Metric, usize); 2] = [
+        (
+            "fairness (1 = every faction wins as often)",
+            |s| Some(s.fairness),
+            2,
+        ),
+        ("mean length of decided matches (s)", |s| s.mean_seconds, 1),
+    ];
+
+    for (title, metric, precision) in tables {
+        let values: Vec<f64> = cells
+            .iter()
+            .filter_map(|c| c.stats.as_ref().and_then(metric))
+            .collect();
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let format = |cell: &Cell| match cell.stats.as_ref().and_then(metric) {
+            Some(value) => format!("{value:>8.precision$}{}", shade(value, min, max)),
+            None => format!("{:>9}", "-"),
+        };
+
+        println!("{title}");
+        if let [(rows, row_values), (columns, column_values)] = vary {
+            // two settings: one per axis
+            let corner = format!("{rows} \\ {columns}");
+            let width = row_values
+                .iter()
+                .map(String::len)
+                .max()
+                .unwrap_or(0)
+                .max(corner.len());
+            print!("{corner:<width$}");
+            for value in column_values {
+                print!(" {value:>9}");
+            }
+            println!();
+            for (row, chunk) in row_values.iter().zip(cells.chunks(column_values.len())) {
+                print!("{row:<width$}");
+                for cell in chunk {
+                    print!(" {}", format(cell));
+                }
+                println!();
+            }
+        } else {
+            let labels: Vec<String> = cells.iter().map(|c| label(vary, &c.values)).collect();
+            let width = labels.iter().map(String::len).max().unwrap_or(0);
+            for (label, cell) in labels.iter().zip(cells) {
+                println!("{label:<width$} {}", format(cell));
+            }
+        }
+        println!();
+    }
+}
+
+fn write_csv(
+    vary: &[(String, Vec<String>)],
+    factions: &Factions,
+    cells: &[Cell],
+    out: &mut impl Write,
+) -> io::Result<()> {
+    for (key, _) in vary {
+        write!(out, "{key},")?;
+    }
+    write!(out, "matches,undecided,fairness,mean_seconds")?;
+    for faction in factions.iter() {
+        write!(out, ",win_rate_{}", factions.name(faction))?;
+    }
+    writeln!(out)?;
+
+    for cell in cells {
+        let Some(stats) = &cell.stats else {
+            continue;
+        };
+        for value in cell.values.iter() {
+            write!(out, "{value},")?;
+        }
+        write!(
+            out,
+            "{},{},{:.4},",
+            stats.matches, stats.undecided, stats.fairness
+        )?;
+        if let Some(seconds) = stats.mean_seconds {
+            write!(out, "{seconds:.4}")?;
+        }
+        for rate in stats.win_rates.iter() {
+            write!(out, ",{rate:.4}")?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(winner: Option<usize>, seconds: f32) -> MatchResult {
+        MatchResult {
+            seed: 0,
+            winner,
+            ticks: 0,
+            seconds,
+            conversions: 0,
+            peak: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn fairness_from_even_to_one_sided() {
+        assert!((fairness(&[5, 5, 5]) - 1.).abs() < 1e-9);
+        assert!(fairness(&[9, 0, 0]).abs() < 1e-9);
+        assert!((fairness(&[2, 1, 0]) - 0.5).abs() < 1e-9);
+        assert_eq!(fairness(&[0, 0]), 0.);
+        assert_eq!(fairness(&[4]), 0.);
+    }
+
+    #[test]
+    fn grid_varies_the_first_key_slowest() {
+        let vary = [
+            ("a".to_owned(), vec!["1".to_owned(), "2".to_owned()]),
+            (
+                "b".to_owned(),
+                vec!["x".to_owned(), "y".to_owned(), "z".to_owned()],
+            ),
+        ];
+        let combinations: Vec<String> = grid(&vary).iter().map(|c| c.join("")).collect();
+        assert_eq!(combinations, ["1x", "1y", "1z", "2x", "2y", "2z"]);
+        assert_eq!(grid(&[]), vec![Vec::<String>::new()]);
+    }
+
+    #[test]
+    fn mean_length_leaves_out_undecided_matches() {
+        let results = [
+            result(Some(0), 10.),
+            result(None, 600.),
+            result(Some(1), 20.),
+        ];
+        assert_eq!(mean_seconds(&results), Some(15.));
+        assert_eq!(mean_seconds(&[result(None, 600.)]), None);
+    }
+}
@@ -0,0 +1,311 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use bevy::prelude::*;
+use bevy_rand::{plugin::EntropyPlugin, prelude::WyRand};
+use serde::Serialize;
+
+use crate::{
+    cli::TournamentArgs,
+    entities::Faction,
+    events::GameOverEvent,
+    plugins::{game::GameplayPlugin, headless::HeadlessPlugin},
+    resources::{
+        config::SimConfig, factions::Factions, Arena, MatchStats, PopulationHistory, Seed,
+    },
+};
+
+/// Outcome of a single headless match.
+#[derive(Serialize, Debug, Clone)]
+pub struct MatchResult {
+    pub seed: u64,
+    /// `None` when the match hit the tick limit.
+    pub winner: Option<usize>,
+    pub ticks: u32,
+    pub seconds: f32,
+    pub conversions: u32,
+    /// Largest population every faction reached, in declaration order.
+    pub peak: Vec<usize>,
+}
+
+/// How often a faction won, with a 95% confidence interval.
+#[derive(Serialize, Debug, Clone)]
+pub struct WinRate {
+    pub faction: String,
+    pub wins: usize,
+    pub rate: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    factions: Vec<&'a str>,
+    matches: &'a [MatchResult],
+    win_rates: &'a [WinRate],
+    undecided: usize,
+}
+
+/// Plays a whole match without a window, as `--headless` would.
+pub fn play_match(
+    seed: u64,
+    factions: &Factions,
+    config: &SimConfig,
+    max_ticks: u32,
+) -> MatchResult {
+    let mut app = App::new();
+    app.insert_resource(Seed(seed))
+        .insert_resource(factions.clone())
+        .insert_resource(config.clone())
+        .insert_resource(Arena::from(config))
+        .add_plugins(HeadlessPlugin {
+            max_ticks,
+            report: false,
+        })
+        .add_plugins(GameplayPlugin)
+        .add_plugins(EntropyPlugin::<WyRand>::with_seed(seed.to_le_bytes()));
+    app.finish();
+    app.cleanup();
+
+    let mut cursor = app.world().resource::<Events<GameOverEvent>>().get_cursor();
+    let mut winner = None;
+    while app.should_exit().is_none() {
+        app.update();
+        let events = app.world().resource::<Events<GameOverEvent>>();
+        if let Some(result) = cursor.read(events).last() {
+            winner = Some(result.winner.0);
+        }
+    }
+
+    let world = app.world();
+    let stats = world.resource::<MatchStats>();
+    let mut peak = vec![0; factions.len()];
+    for (_, counts) in world.resource::<PopulationHistory>().samples.iter() {
+        for (peak, &count) in peak.iter_mut().zip(counts) {
+            *peak = (*peak).max(count);
+        }
+    }
+
+    MatchResult {
+        seed,
+        winner,
+        ticks: stats.ticks,
+        seconds: stats.elapsed.as_secs_f32(),
+        conversions: stats.conversions,
+        peak,
+    }
+}
+
+/// Plays `count` matches from `first_seed` onwards on `jobs` threads, sorted
+/// by seed.
+pub fn play_matches(
+    first_seed: u64,
+    count: u64,
+    jobs: usize,
+    factions: &Factions,
+    config: &SimConfig,
+    max_ticks: u32,
+) -> Vec<MatchResult> {
+    let next = AtomicU64::new(0);
+    let results = Mutex::new(Vec::new());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.max(1) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let seed = match first_seed.checked_add(index) {
+                    Some(seed) if index < count => seed,
+                    _ => break,
+                };
+                let result = play_match(seed, factions, config, max_ticks);
+                results.lock().unwrap().push(result);
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|result| result.seed);
+    results
+}
+
+/// Rejects seed ranges running past the largest seed.
+pub fn check_seeds(first_seed: u64, count: u64) -> Result<(), String> {
+    match first_seed.checked_add(count.saturating_sub(1)) {
+        Some(_) => Ok(()),
+        None => Err(format!(
+            "{count} seeds from {first_seed} run past the largest seed ({})",
+            u64::MAX
+        )),
+    }
+}
+
+/// 95% Wilson score interval of `wins` successes out of `total` tries.
+pub fn wilson_interval(wins: usize, total: usize) -> (f64, f64) {
+    if total == 0 {
+        return (0., 1.);
+    }
+
+    let z = 1.96_f64;
+    let n = total as f64;
+    let p = wins as f64 / n;
+    let denominator = 1. + z * z / n;
+    let center = (p + z * z / (2. * n)) / denominator;
+    let half = z * (p * (1. - p) / n + z * z / (4. * n * n)).sqrt() / denominator;
+    ((center - half).max(0.), (center + half).min(1.))
+}
+
+pub fn win_rates(factions: &Factions, results: &[MatchResult]) -> Vec<WinRate> {
+    factions
+        .iter()
+        .map(|faction| {
+            let wins = results
+                .iter()
+                .filter(|result| result.winner == Some(faction.0))
+                .count();
+            let (low, high) = wilson_interval(wins, results.len());
+            WinRate {
+                faction: factions.name(faction).to_owned(),
+                wins,
+                rate: wins as f64 / results.len().max(1) as f64,
+                low,
+                high,
+            }
+        })
+        .collect()
+}
+
+pub fn run(args: &TournamentArgs, factions: &Factions, config: &SimConfig, max_ticks: u32) {
+    let jobs = args
+        .jobs
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+    let results = play_matches(
+        args.first_seed,
+        args.matches,
+        jobs,
+        factions,
+        config,
+        max_ticks,
+    );
+    let rates = win_rates(factions, &results);
+    let undecided = results.iter().filter(|r| r.winner.is_none()).count();
+
+    println!();
+    println!(
+        "{:<12} {:>6} {:>9}   95% interval",
+        "faction", "wins", "win rate"
+    );
+    for rate in rates.iter() {
+        println!(
+            "{:<12} {:>6} {:>8.1}%   {:.1}% - {:.1}%",
+            rate.faction,
+            rate.wins,
+            rate.rate * 100.,
+            rate.low * 100.,
+            rate.high * 100.
+        );
+    }
+    if undecided > 0 {
+        println!("{undecided} matches hit the tick limit");
+    }
+
+    let report = Report {
+        factions: factions.iter().map(|f| factions.name(f)).collect(),
+        matches: &results,
+        win_rates: &rates,
+        undecided,
+    };
+    if let Some(path) = &args.json {
+        write_file(path, |out| {
+            serde_json::to_writer_pretty(&mut *out, &report).map_err(io::Error::from)
+        });
+    }
+    if let Some(path) = &args.matches_csv {
+        write_file(path, |out| write_matches_csv(factions, &results, out));
+    }
+    if let Some(path) = &args.summary_csv {
+        write_file(path, |out| write_rates_csv(&rates, undecided, out));
+    }
+}
+
+fn write_matches_csv(
+    factions: &Factions,
+    results: &[MatchResult],
+    out: &mut impl Write,
+) -> io::Result<()> {
+    write!(out, "seed,winner,ticks,seconds,conversions")?;
+    for faction in factions.iter() {
+        write!(out, ",peak_{}", factions.name(faction))?;
+    }
+    writeln!(out)?;
+
+    for result in results {
+        let winner = result.winner.map_or("", |w| factions.name(Faction(w)));
+        write!(
+            out,
+            "{},{},{},{:.4},{}",
+            result.seed, winner, result.ticks, result.seconds, result.conversions
+        )?;
+        for peak in result.peak.iter() {
+            write!(out, ",{peak}")?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn write_rates_csv(rates: &[WinRate], undecided: usize, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "faction,wins,win_rate,ci_low,ci_high")?;
+    for rate in rates {
+        writeln!(
+            out,
+            "{},{},{:.4},{:.4},{:.4}",
+            rate.faction, rate.wins, rate.rate, rate.low, rate.high
+        )?;
+    }
+    writeln!(out, "undecided,{undecided},,,")
+}
+
+pub(crate) fn write_file(path: &Path, write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>) {
+    let result = File::create(path).and_then(|file| {
+        let mut out = BufWriter::new(file);
+        write(&mut out)?;
+        out.flush()
+    });
+    match result {
+        Ok(()) => println!("written to {}", path.display()),
+        Err(e) => eprintln!("cannot write {}: {e}", path.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close((low, high): (f64, f64), expected: (f64, f64)) {
+        assert!((low - expected.0).abs() < 1e-4, "{low} != {}", expected.0);
+        assert!((high - expected.1).abs() < 1e-4, "{high} != {}", expected.1);
+    }
+
+    #[test]
+    fn wilson_interval_known_values() {
+        assert_close(wilson_interval(0, 10), (0., 0.2775));
+        assert_close(wilson_interval(5, 10), (0.2366, 0.7634));
+        assert_close(wilson_interval(10, 10), (0.7225, 1.));
+        assert_eq!(wilson_interval(0, 0), (0., 1.));
+    }
+
+    #[test]
+    fn seed_ranges_stop_at_the_largest_seed() {
+        assert!(check_seeds(0, 100).is_ok());
+        assert!(check_seeds(u64::MAX - 10, 11).is_ok());
+        assert!(check_seeds(u64::MAX - 10, 12).is_err());
+    }
+}
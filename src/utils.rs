@@ -1,5 +1,8 @@
+use bevy::math::{vec2, Vec2};
 use rand::Rng;
 
+use crate::{entities::Faction, resources::Region};
+
 pub fn on_generic_borders(entity: f32, borders: f32) -> bool {
     entity.abs() >= borders.abs()
 }
@@ -10,33 +13,102 @@ pub fn on_borders(entity: (f32, f32), borders: (f32, f32)) -> bool {
     on_generic_borders(x, width) || on_generic_borders(y, height)
 }
 
-#[macro_export]
-macro_rules! add_components {
-    ($entity:ident, $enemy:ident, $target:ident) => {
-        impl HasEnemy for $entity {
-            type Enemy = $enemy;
-        }
+// candidates tried for a region before settling for the roomiest one
+const REGION_ATTEMPTS: usize = 30;
+
+/// Places `count` regions of `radius` inside `bounds`, handed out to the
+/// factions in turn. Each region is kept `spacing` apart (edge to edge) from
+/// every region placed before it if one of `REGION_ATTEMPTS` random
+/// candidates has room for it; otherwise the candidate farthest from them is
+/// used, so regions still overlap in an arena too crowded to hold them all.
+/// A region always lies whole inside `bounds` when `radius` fits in them.
+pub fn generate_regions(
+    bounds: Vec2,
+    count: usize,
+    radius: f32,
+    spacing: f32,
+    factions: usize,
+    rng: &mut impl Rng,
+) -> Vec<Region> {
+    let mut regions: Vec<Region> = Vec::with_capacity(count);
+    let extent = (bounds - radius).max(Vec2::ZERO);
+    let distance = 2. * radius + spacing;
+
+    for i in 0..count {
+        // distance to the closest region placed so far
+        let clearance = |center: Vec2| {
+            regions
+                .iter()
+                .map(|region| region.center.distance(center))
+                .fold(f32::INFINITY, f32::min)
+        };
 
-        impl HasTarget for $entity {
-            type Target = $target;
+        let mut best = (Vec2::ZERO, f32::NEG_INFINITY);
+        for _ in 0..REGION_ATTEMPTS {
+            let center = vec2(
+                rng.gen_range(-extent.x..=extent.x),
+                rng.gen_range(-extent.y..=extent.y),
+            );
+            let room = clearance(center);
+            if room > best.1 {
+                best = (center, room);
+            }
+            if room >= distance {
+                break;
+            }
         }
-    };
+
+        regions.push(Region {
+            center: best.0,
+            radius,
+            faction: Faction(i % factions.max(1)),
+        });
+    }
+
+    regions
 }
 
-pub fn generate_regions(width: f32, height: f32, count: usize) -> Vec<(f32, f32, f32)> {
-    let mut rng = rand::thread_rng();
-    let mut regions = Vec::new();
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
 
-    let radius = 60.;
+    fn inside(regions: &[Region], bounds: Vec2) -> bool {
+        regions
+            .iter()
+            .all(|region| (region.center.abs() + region.radius).cmple(bounds).all())
+    }
 
-    let width = width - radius;
-    let height = height - radius;
+    #[test]
+    fn regions_keep_apart_when_there_is_room() {
+        let bounds = vec2(600., 400.);
+        let mut rng = StdRng::seed_from_u64(7);
+        let regions = generate_regions(bounds, 12, 30., 20., 3, &mut rng);
 
-    while regions.len() < count {
-        let x = rng.gen_range(-width..=width);
-        let y = rng.gen_range(-height..=height);
-        regions.push((x, y, radius));
+        assert_eq!(regions.len(), 12);
+        assert!(inside(&regions, bounds));
+        for (i, a) in regions.iter().enumerate() {
+            assert_eq!(a.faction, Faction(i % 3));
+            for b in regions[i + 1..].iter() {
+                assert!(
+                    a.center.distance(b.center) >= 2. * 30. + 20.,
+                    "{a:?} and {b:?} are too close"
+                );
+            }
+        }
     }
 
-    regions
+    #[test]
+    fn crowded_arenas_still_get_every_region() {
+        let bounds = vec2(100., 80.);
+        let mut rng = StdRng::seed_from_u64(7);
+        let regions = generate_regions(bounds, 20, 40., 10., 4, &mut rng);
+
+        assert_eq!(regions.len(), 20);
+        assert!(inside(&regions, bounds));
+        assert!(regions.iter().enumerate().any(|(i, a)| regions[..i]
+            .iter()
+            .any(|b| a.center.distance(b.center) < 2. * 40. + 10.)));
+    }
 }